@@ -0,0 +1,278 @@
+// One line for every request, whoever it came from and however it went, so the pipeline can
+// work out which selectors are popular and how often things go wrong.
+//
+// Lines are either in the common log format, with what was asked for as the request and how
+// long it took on the end, or JSON with the same fields.
+
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self,Write};
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rustc_serialize::json::{Json,ToJson};
+use time;
+
+use protocol::{self,Selected,Selector};
+
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Format {
+    Common,
+    Json,
+}
+
+#[derive(Debug)]
+pub struct ParseFormatError(String);
+
+impl fmt::Display for ParseFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unknown access log format {}, expected common or json", self.0)
+    }
+}
+
+impl error::Error for ParseFormatError {
+    fn description(&self) -> &str {
+        "Unknown access log format"
+    }
+}
+
+impl FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "common" => Ok(Format::Common),
+            "json" => Ok(Format::Json),
+            _ => Err(ParseFormatError(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Outcome {
+    Served,
+    NotFound,
+    // Made no sense, or asked for something frog never does
+    Bad,
+    SlowDown,
+    // Turned away at the door
+    Busy,
+    Refused,
+    // Hung up, took too long or sent something that broke the protocol, or the source could not
+    // get at what was asked for
+    Failed,
+}
+
+impl Outcome {
+    // The closest HTTP status, since that is what log pipelines understand
+    pub fn status(&self) -> u16 {
+        match *self {
+            Outcome::Served => 200,
+            Outcome::Bad => 400,
+            Outcome::Refused => 403,
+            Outcome::NotFound => 404,
+            Outcome::SlowDown => 429,
+            Outcome::Failed => 500,
+            Outcome::Busy => 503,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Outcome::Served => "served",
+            Outcome::NotFound => "not_found",
+            Outcome::Bad => "bad",
+            Outcome::SlowDown => "slow_down",
+            Outcome::Busy => "busy",
+            Outcome::Refused => "refused",
+            Outcome::Failed => "failed",
+        }
+    }
+}
+
+// What a frontend sent back for one request
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct Served {
+    // The selector, with any search terms after a tab
+    pub selector: String,
+    // The gopher item type of what was sent, whichever protocol it went over
+    pub item_type: char,
+    pub outcome: Outcome,
+    pub bytes: usize,
+}
+
+impl Served {
+    pub fn new<S: Into<String>>(selector: S, item_type: char, outcome: Outcome) -> Served {
+        Served {
+            selector: selector.into(),
+            item_type: item_type,
+            outcome: outcome,
+            bytes: 0,
+        }
+    }
+
+    // For whatever a source found, with binaries sent as the given type
+    pub fn found(selector: &Selector, selected: &Selected, binary_type: char) -> Served {
+        let (item_type, outcome) = match selected {
+            &Selected::SlowDown => ('3', Outcome::SlowDown),
+            &Selected::Error(_) => ('3', Outcome::NotFound),
+            &Selected::Failed(_) => ('3', Outcome::Failed),
+            &Selected::Text(_) => ('0', Outcome::Served),
+            &Selected::Binary(_) => (binary_type, Outcome::Served),
+            &Selected::TempMenu(_) | &Selected::ForeverMenu(_) => ('1', Outcome::Served),
+        };
+        Served::new(describe(selector), item_type, outcome)
+    }
+
+    // Nothing was read, or nothing could be
+    pub fn nothing(outcome: Outcome) -> Served {
+        Served::new("", '-', outcome)
+    }
+
+    pub fn sent(mut self, bytes: usize) -> Served {
+        self.bytes = bytes;
+        self
+    }
+}
+
+pub fn describe(selector: &Selector) -> String {
+    match selector {
+        &Selector::Path(ref path) => match path.extra() {
+            Some(extra) => format!("{}\t{}", path.val(), extra),
+            None => path.val().clone(),
+        },
+        &Selector::Empty => String::new(),
+    }
+}
+
+// The type gophers get a binary as, going by its name
+pub fn gopher_binary_type(selector: &Selector) -> char {
+    match selector {
+        &Selector::Path(ref path) => protocol::binary_type(path.val()).unwrap_or('9'),
+        &Selector::Empty => '9',
+    }
+}
+
+// Quotes and anything unprintable are escaped, so a selector can't end the request early
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1000000) as u64
+}
+
+// Peers nobody knows the address of are logged as missing
+fn format_record(format: Format, now: &time::Tm, peer: Option<IpAddr>, frontend: &str, served: &Served, took: Duration) -> String {
+    match format {
+        Format::Common => {
+            let selector = if served.selector.is_empty() { "-".to_string() } else { escape(&served.selector) };
+            format!("{} - - [{}] \"{} {} {}\" {} {} {}",
+                    peer.map_or("-".to_string(), |peer| peer.to_string()), time::strftime("%d/%b/%Y:%H:%M:%S +0000", now).unwrap(), served.item_type, selector,
+                    frontend.to_uppercase(), served.outcome.status(), served.bytes, millis(took))
+        },
+        Format::Json => {
+            let mut record = BTreeMap::new();
+            record.insert("time".to_string(), now.rfc3339().to_string().to_json());
+            record.insert("peer".to_string(), peer.map(|peer| peer.to_string()).to_json());
+            record.insert("frontend".to_string(), frontend.to_json());
+            record.insert("selector".to_string(), served.selector.to_json());
+            record.insert("item_type".to_string(), served.item_type.to_string().to_json());
+            record.insert("outcome".to_string(), served.outcome.name().to_json());
+            record.insert("status".to_string(), served.outcome.status().to_json());
+            record.insert("bytes".to_string(), served.bytes.to_json());
+            record.insert("millis".to_string(), millis(took).to_json());
+            Json::Object(record).to_string()
+        },
+    }
+}
+
+pub struct AccessLog {
+    format: Format,
+    out: Mutex<Box<Write + Send>>,
+}
+
+impl AccessLog {
+    pub fn new(format: Format, out: Box<Write + Send>) -> AccessLog {
+        AccessLog {
+            format: format,
+            out: Mutex::new(out),
+        }
+    }
+
+    // Appends to the file, so rotating it is up to whoever rotates logs
+    pub fn open<P: AsRef<Path>>(format: Format, file: P) -> io::Result<AccessLog> {
+        let out = try!(OpenOptions::new().create(true).append(true).open(file));
+        Ok(AccessLog::new(format, Box::new(out)))
+    }
+
+    pub fn record(&self, peer: Option<IpAddr>, frontend: &str, served: &Served, took: Duration) {
+        let line = format_record(self.format, &time::now_utc(), peer, frontend, served, took);
+        let mut out = self.out.lock().unwrap();
+        if let Err(why) = writeln!(out, "{}", line).and_then(|_| out.flush()) {
+            error!("FROG COULD NOT WRITE TO THE ACCESS LOG: {}", why);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use time;
+
+    use protocol::{Path,Selected,Selector};
+    use super::{format_record,Format,Outcome,Served};
+
+
+    #[test]
+    fn records_are_common_or_json() {
+        let now = time::at_utc(time::Timespec::new(1500000000, 0));
+        let peer = Some("192.0.2.1".parse().unwrap());
+        let served = Served::new("/DOCS/FROG_MODELS", '0', Outcome::Served).sent(1234);
+
+        assert_eq!(format_record(Format::Common, &now, peer, "gopher", &served, Duration::from_millis(12)),
+                   "192.0.2.1 - - [14/Jul/2017:02:40:00 +0000] \"0 /DOCS/FROG_MODELS GOPHER\" 200 1234 12");
+        assert_eq!(format_record(Format::Json, &now, peer, "gopher", &served, Duration::from_millis(12)),
+                   "{\"bytes\":1234,\"frontend\":\"gopher\",\"item_type\":\"0\",\"millis\":12,\"outcome\":\"served\",\
+                    \"peer\":\"192.0.2.1\",\"selector\":\"/DOCS/FROG_MODELS\",\"status\":200,\"time\":\"2017-07-14T02:40:00Z\"}");
+
+        let search = Served::new("/TIP/SEARCH\t\"FROG\"", '1', Outcome::Served);
+        assert_eq!(format_record(Format::Common, &now, peer, "http", &search, Duration::from_secs(1)),
+                   "192.0.2.1 - - [14/Jul/2017:02:40:00 +0000] \"1 /TIP/SEARCH\\t\\\"FROG\\\" HTTP\" 200 0 1000");
+
+        assert_eq!(format_record(Format::Common, &now, None, "gopher", &served, Duration::from_millis(12)),
+                   "- - - [14/Jul/2017:02:40:00 +0000] \"0 /DOCS/FROG_MODELS GOPHER\" 200 1234 12");
+        assert!(format_record(Format::Json, &now, None, "gopher", &served, Duration::from_millis(12)).contains("\"peer\":null,"));
+    }
+
+    #[test]
+    fn outcomes_follow_what_was_found() {
+        let selector = Selector::Path(Path::from("/NOTHING"));
+        let missing = Served::found(&selector, &Selected::Error(Box::new("/NOTHING NOT FOUND".to_string())), '9');
+        assert_eq!((missing.item_type, missing.outcome), ('3', Outcome::NotFound));
+
+        let slow = Served::found(&selector, &Selected::SlowDown, '9');
+        assert_eq!(slow.outcome, Outcome::SlowDown);
+
+        let image = Served::found(&selector, &Selected::Binary(Box::new(vec![])), 'I');
+        assert_eq!((image.item_type, image.outcome), ('I', Outcome::Served));
+    }
+}
@@ -0,0 +1,107 @@
+// How fast frog can read selectors, alone and with a crowd of gophers
+//
+// These need a nightly compiler: cargo +nightly bench --features bench
+
+extern crate test;
+
+use std::io::{self,Cursor,Read,Write};
+use std::net::{SocketAddr,TcpListener,TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+use self::test::Bencher;
+use futures::Future;
+use tokio::io::AsyncRead;
+
+use protocol::{ExternalAddr,Protocol};
+use mock_frog_tips;
+use gopher_pool::GopherPool;
+use the_impl_ya_dummy::Gopher;
+use super::gopher_it_ha_ha_puns;
+
+
+const CLIENTS: usize = 32;
+const SELECTOR: &'static str = "/TIP/SEARCH\tFROG IS NOT A TOY\r\n";
+
+// Never has more than a byte to give at a time
+struct Trickle<'a>(Cursor<&'a [u8]>);
+
+impl<'a> Read for Trickle<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = if buf.is_empty() { 0 } else { 1 };
+        self.0.read(&mut buf[..len])
+    }
+}
+
+impl<'a> AsyncRead for Trickle<'a> {}
+
+fn parse<R: AsyncRead, F: Fn() -> R>(b: &mut Bencher, reader: F) {
+    let ext_addr = ExternalAddr::new("127.0.0.1", 70);
+    b.bytes = SELECTOR.len() as u64;
+    b.iter(|| Protocol::new(&ext_addr, 1024).read(reader()).wait().unwrap().2);
+}
+
+#[bench]
+fn parse_a_byte_at_a_time(b: &mut Bencher) {
+    parse(b, || Trickle(Cursor::new(SELECTOR.as_bytes())));
+}
+
+#[bench]
+fn parse_in_bulk(b: &mut Bencher) {
+    parse(b, || Cursor::new(SELECTOR.as_bytes()));
+}
+
+// Starts a frog that plays with gophers until the benchmarks are over
+fn start_frog() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let ext_addr = ExternalAddr::new("127.0.0.1", addr.port());
+    let gopher = Arc::new(Gopher::new(ext_addr, "testing".to_string(), &mock_frog_tips::hole_without_tips()));
+
+    thread::spawn(move || {
+        let pool = GopherPool::new(gopher, CLIENTS, CLIENTS, CLIENTS).unwrap();
+        for stream_res in listener.incoming() {
+            let _ = gopher_it_ha_ha_puns(stream_res, &pool);
+        }
+    });
+
+    addr
+}
+
+// Every client asks for the same thing at once, either all in one go or a byte at a time
+fn crowd(b: &mut Bencher, slow: bool) {
+    let addr = start_frog();
+    b.bytes = (CLIENTS * SELECTOR.len()) as u64;
+    b.iter(|| {
+        let clients: Vec<_> = (0..CLIENTS).map(|_| thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            if slow {
+                stream.set_nodelay(true).unwrap();
+                for byte in SELECTOR.as_bytes() {
+                    stream.write_all(&[*byte]).unwrap();
+                    stream.flush().unwrap();
+                }
+            } else {
+                stream.write_all(SELECTOR.as_bytes()).unwrap();
+            }
+
+            let mut resp = Vec::new();
+            stream.read_to_end(&mut resp).unwrap();
+            resp.len()
+        })).collect();
+
+        for client in clients {
+            assert!(client.join().unwrap() > 0);
+        }
+    });
+}
+
+#[bench]
+fn many_fast_clients(b: &mut Bencher) {
+    crowd(b, false);
+}
+
+#[bench]
+fn many_slow_clients(b: &mut Bencher) {
+    crowd(b, true);
+}
@@ -0,0 +1,162 @@
+// Blocks of addresses, written the usual way: 10.0.0.0/8, ::1/128 or just 192.168.1.1
+
+use std::error;
+use std::fmt;
+use std::net::{AddrParseError,IpAddr,Ipv4Addr};
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u32,
+}
+
+// Both kinds of address as one big number, and how many bits it has
+fn bits(addr: &IpAddr) -> (u128, u32) {
+    match *addr {
+        IpAddr::V4(ip) => (u32::from(ip) as u128, 32),
+        IpAddr::V6(ip) => (u128::from(ip), 128),
+    }
+}
+
+// IPv4 that came in over IPv6, as ::ffff:a.b.c.d, is still IPv4
+pub fn unmapped(addr: &IpAddr) -> IpAddr {
+    match *addr {
+        IpAddr::V6(v6) => {
+            let s = v6.segments();
+            if s[..5] == [0, 0, 0, 0, 0] && s[5] == 0xffff {
+                let o = v6.octets();
+                IpAddr::V4(Ipv4Addr::new(o[12], o[13], o[14], o[15]))
+            } else {
+                *addr
+            }
+        },
+        IpAddr::V4(_) => *addr,
+    }
+}
+
+impl Cidr {
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        let (block, width) = bits(&self.addr);
+        let (other, other_width) = bits(&unmapped(addr));
+        if width != other_width {
+            return false;
+        }
+
+        if self.prefix_len == 0 {
+            return true;
+        }
+        let shift = width - self.prefix_len;
+        (block >> shift) == (other >> shift)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseCidrError {
+    BadAddr(AddrParseError),
+    BadPrefix(ParseIntError),
+    PrefixTooLong(u32),
+}
+
+impl fmt::Display for ParseCidrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseCidrError::BadAddr(ref err) => write!(f, "Invalid address: {}", err),
+            ParseCidrError::BadPrefix(ref err) => write!(f, "Invalid prefix length: {}", err),
+            ParseCidrError::PrefixTooLong(len) => write!(f, "Prefix length {} is longer than the address", len),
+        }
+    }
+}
+
+impl error::Error for ParseCidrError {
+    fn description(&self) -> &str {
+        match *self {
+            ParseCidrError::BadAddr(ref err) => err.description(),
+            ParseCidrError::BadPrefix(ref err) => err.description(),
+            ParseCidrError::PrefixTooLong(_) => "Prefix length is longer than the address",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            ParseCidrError::BadAddr(ref err) => Some(err),
+            ParseCidrError::BadPrefix(ref err) => Some(err),
+            ParseCidrError::PrefixTooLong(_) => None,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = ParseCidrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '/');
+        let addr: IpAddr = try!(parts.next().unwrap_or("").parse().map_err(ParseCidrError::BadAddr));
+        let width = bits(&addr).1;
+
+        // A lone address is a block of one
+        let prefix_len = match parts.next() {
+            Some(len) => try!(len.parse().map_err(ParseCidrError::BadPrefix)),
+            None => width,
+        };
+        if prefix_len > width {
+            return Err(ParseCidrError::PrefixTooLong(prefix_len));
+        }
+
+        Ok(Cidr {
+            addr: addr,
+            prefix_len: prefix_len,
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::Cidr;
+
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn blocks_contain_their_addresses() {
+        let block: Cidr = "10.1.0.0/16".parse().unwrap();
+        assert!(block.contains(&ip("10.1.2.3")));
+        assert!(!block.contains(&ip("10.2.0.1")));
+        assert!(!block.contains(&ip("::1")));
+
+        let one: Cidr = "::1".parse().unwrap();
+        assert!(one.contains(&ip("::1")));
+        assert!(!one.contains(&ip("::2")));
+
+        let everything: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(everything.contains(&ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn ipv4_over_ipv6_is_still_ipv4() {
+        let block: Cidr = "127.0.0.0/8".parse().unwrap();
+        assert!(block.contains(&ip("::ffff:127.0.0.1")));
+        assert!(!block.contains(&ip("::ffff:192.0.2.1")));
+        assert!(!block.contains(&ip("::127.0.0.1")));
+    }
+
+    #[test]
+    fn bad_blocks_are_refused() {
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0.0/EIGHT".parse::<Cidr>().is_err());
+        assert!("FROG/8".parse::<Cidr>().is_err());
+    }
+}
@@ -0,0 +1,162 @@
+// Tips for anyone who still fingers people.
+//
+// `finger tip@host` gets a random tweeted tip, `finger 1234@host` gets tip #1234
+// and plain `finger @host` gets the README.
+
+use std::io;
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use futures::{future,Future};
+use tokio::io::{AsyncRead,AsyncWrite};
+
+use protocol::{Path,Protocol,Selected,Selector};
+use the_impl_ya_dummy::{Gopher,hang_up};
+use hole_config::README;
+use rate_limit::SLOW_DOWN;
+use access_log::{Outcome,Served};
+
+
+// Queries are user names, so they had better be short
+const MAX_LINE_LEN: usize = 128;
+
+fn crlf(text: &str) -> String {
+    format!("{}\r\n", text.trim_right_matches('\n').replace("\n", "\r\n"))
+}
+
+// Which tip the query is after and what to say if there isn't one, or what to say instead
+fn route(query: &str) -> Result<(Selector, String), (String, Served)> {
+    // Verbose requests get the same answer, since frog is never anything but verbose
+    let query = query.trim();
+    let query = if query.starts_with("/W") && query[2..].chars().next().map_or(true, char::is_whitespace) {
+        query[2..].trim()
+    } else {
+        query
+    };
+
+    let (selector, missing) = if query.is_empty() {
+        return Err((crlf(README), Served::new(query, '0', Outcome::Served)));
+    } else if query.contains('@') {
+        return Err((crlf("FROG DOES NOT PASS FINGERS ALONG."), Served::new(query, '3', Outcome::Bad)));
+    } else if query.eq_ignore_ascii_case("tip") {
+        ("/TIP/RANDOM".to_string(), "FROG HAS NO TIPS RIGHT NOW.".to_string())
+    } else if let Ok(number) = query.parse::<u64>() {
+        (format!("/TIP/{}", number), format!("FROG COULD NOT FIND TIP #{}.", number))
+    } else {
+        return Err((crlf(&format!("FROG DOES NOT KNOW {}. TRY tip OR A TIP NUMBER.", query.to_uppercase())),
+                    Served::new(query, '3', Outcome::Bad)));
+    };
+
+    Ok((Selector::Path(Path::from(selector)), missing))
+}
+
+fn answer(gopher: Arc<Gopher>, peer: IpAddr, query: &str) -> Box<Future<Item=(String, Served), Error=io::Error> + Send> {
+    let (selector, missing) = match route(query) {
+        Ok(route) => route,
+        Err(answered) => return Box::new(future::ok(answered)),
+    };
+
+    Gopher::find_then(gopher, Some(peer), selector, move |_, selector, found| {
+        let served = Served::found(selector, &found, '9');
+        Ok(match found {
+            Selected::Text(text) => (crlf(&text), served),
+            Selected::SlowDown => (crlf(SLOW_DOWN), served),
+            Selected::Failed(ref why) => (crlf(why), served),
+            _ => (crlf(&missing), Served { outcome: Outcome::NotFound, item_type: '3', ..served }),
+        })
+    })
+}
+
+// Reads the query and answers it with a tip or the README
+pub fn respond<S>(gopher: Arc<Gopher>, stream: S, peer: IpAddr) -> Box<Future<Item=Served, Error=io::Error> + Send>
+    where S: AsyncRead + AsyncWrite + Send + 'static {
+    let reader = gopher.clone();
+    let read = Protocol::new(gopher.ext_addr(), MAX_LINE_LEN).read(stream).map_err(move |why| {
+        reader.metrics().protocol_error(why.name(), &why.to_string());
+        io::Error::from(why)
+    });
+
+    let answered = read.and_then(move |(stream, _, selector)| {
+        let query = match selector {
+            Selector::Path(ref path) => match path.extra() {
+                Some(extra) => format!("{} {}", path.val(), extra),
+                None => path.val().clone(),
+            },
+            Selector::Empty => String::new(),
+        };
+        answer(gopher, peer, &query).map(move |(resp, served)| (stream, resp, served))
+    });
+
+    Box::new(answered.and_then(|(stream, resp, served)| hang_up(stream, resp.into_bytes(), served)))
+}
+
+// Sends the finger away without listening to what it wanted
+pub fn turn_away<W: Write>(mut stream: W, why: &str) -> io::Result<()> {
+    stream.write_all(crlf(why).as_bytes())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read,Write};
+    use std::net::{TcpListener,TcpStream};
+    use std::path;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration,Instant};
+
+    use mock_frog_tips::{self,MockFrogTips};
+    use hole_config::{HoleConfig,README};
+    use protocol::ExternalAddr;
+    use gopher_pool::{Frontend,GopherPool};
+    use the_impl_ya_dummy::Gopher;
+    use super::{crlf,route};
+
+
+    fn finger(gopher: &Arc<Gopher>, query: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let pool = GopherPool::new(gopher.clone(), 1, 1, 1).unwrap();
+
+        let mut stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        pool.dispatch_to(listener.accept().unwrap().0, Frontend::Finger, None).unwrap();
+
+        write!(stream, "{}\r\n", query).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+        pool.join();
+        resp
+    }
+
+    #[test]
+    fn fingers_get_tips() {
+        let mock = MockFrogTips::start(mock_frog_tips::canned);
+        let hole = format!("[[source]]\nkind = \"tips\"\napi = \"{}\"\nrefresh = 3600\n", mock.url());
+        let hole = HoleConfig::parse(&hole, path::Path::new(".")).unwrap();
+        let gopher = Arc::new(Gopher::new(ExternalAddr::new("127.0.0.1", 70), mock_frog_tips::API_KEY.to_string(), &hole));
+
+        let started = Instant::now();
+        let mut random = finger(&gopher, "tip");
+        while random == "FROG HAS NO TIPS RIGHT NOW.\r\n" && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(50));
+            random = finger(&gopher, "tip");
+        }
+        assert!(["FROG IS NOT A TOY.\r\n", "FROG IS A TOY.\r\n", "NEVER MICROWAVE FROG.\r\n"].contains(&random.as_str()));
+
+        assert_eq!(finger(&gopher, "3"), "NEVER MICROWAVE FROG.\r\n");
+        assert_eq!(finger(&gopher, "/W 3"), "NEVER MICROWAVE FROG.\r\n");
+        assert_eq!(finger(&gopher, "/W"), crlf(README));
+        assert_eq!(finger(&gopher, "5"), "FROG COULD NOT FIND TIP #5.\r\n");
+        assert_eq!(finger(&gopher, "frog@elsewhere"), "FROG DOES NOT PASS FINGERS ALONG.\r\n");
+        assert_eq!(finger(&gopher, ""), crlf(README));
+    }
+
+    #[test]
+    fn only_a_lone_w_is_verbose() {
+        assert!(route("/W\t3").is_ok());
+        match route("/WALDO") {
+            Err((resp, _)) => assert_eq!(resp, "FROG DOES NOT KNOW /WALDO. TRY tip OR A TIP NUMBER.\r\n"),
+            Ok(_) => panic!("/WALDO is not a tip"),
+        }
+    }
+}
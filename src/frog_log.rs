@@ -1,31 +1,291 @@
 extern crate log;
 
 use time;
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs::{File,OpenOptions};
+use std::io::{self,Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
 use std::thread;
-use log::{LogRecord,LogLevel,LogLevelFilter,LogMetadata,SetLoggerError};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use log::{LogRecord,LogLevel,LogLevelFilter,LogMetadata};
+use rustc_serialize::json::{Json,ToJson};
 
 
-struct FrogLogger;
+#[derive(Debug)]
+pub struct ParseLogConfigError(String);
+
+impl fmt::Display for ParseLogConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl error::Error for ParseLogConfigError {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+fn level(name: &str) -> Result<LogLevelFilter, ParseLogConfigError> {
+    name.parse().map_err(|_| ParseLogConfigError(format!("Unknown log level {}", name)))
+}
+
+// A level for everything, and others for some modules: `warn,tip_source=debug`
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub struct Levels {
+    default: LogLevelFilter,
+    modules: Vec<(String, LogLevelFilter)>,
+}
+
+// How deep into the target the module reaches, if it names the target or any run of modules
+// in it. So tip_source reaches all the way into frog_gopher::the_impl_ya_dummy::tip_source.
+fn reach(module: &str, target: &str) -> Option<(usize, usize)> {
+    let module: Vec<&str> = module.split("::").collect();
+    let target: Vec<&str> = target.split("::").collect();
+    target.windows(module.len())
+          .rposition(|run| run == &module[..])
+          .map(|start| (start + module.len(), module.len()))
+}
+
+impl Levels {
+    pub fn level_for(&self, target: &str) -> LogLevelFilter {
+        // The module that reaches deepest wins
+        self.modules.iter()
+                    .filter_map(|&(ref module, level)| reach(module, target).map(|reach| (reach, level)))
+                    .max_by_key(|&(reach, _)| reach)
+                    .map(|(_, level)| level)
+                    .unwrap_or(self.default)
+    }
+
+    fn max(&self) -> LogLevelFilter {
+        self.modules.iter().map(|&(_, level)| level).fold(self.default, |max, level| if level > max { level } else { max })
+    }
+}
+
+impl Default for Levels {
+    fn default() -> Levels {
+        Levels {
+            default: LogLevelFilter::Info,
+            modules: vec![],
+        }
+    }
+}
+
+impl FromStr for Levels {
+    type Err = ParseLogConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut levels = Levels::default();
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.find('=') {
+                Some(eq) => levels.modules.push((part[..eq].to_string(), try!(level(&part[eq + 1..])))),
+                None => levels.default = try!(level(part)),
+            }
+        }
+        Ok(levels)
+    }
+}
+
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub enum Output {
+    Stdout,
+    Stderr,
+    Syslog,
+    // Appended to
+    File(PathBuf),
+}
+
+impl FromStr for Output {
+    type Err = ParseLogConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stdout" => Ok(Output::Stdout),
+            "stderr" => Ok(Output::Stderr),
+            "syslog" => Ok(Output::Syslog),
+            "" => Err(ParseLogConfigError("Empty log output".to_string())),
+            file => Ok(Output::File(PathBuf::from(file))),
+        }
+    }
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Format {
+    Text,
+    // One object a line, with time, thread, level, module and message
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = ParseLogConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(ParseLogConfigError(format!("Unknown log format {}, expected text or json", s))),
+        }
+    }
+}
+
+#[derive(Clone,Debug,Default)]
+pub struct LogConfig {
+    pub levels: Levels,
+    pub output: Output,
+    pub format: Format,
+}
+
+impl Default for Output {
+    fn default() -> Output {
+        Output::Stdout
+    }
+}
+
+impl Default for Format {
+    fn default() -> Format {
+        Format::Text
+    }
+}
+
+enum Sink {
+    Stdout,
+    Stderr,
+    File(File),
+    #[cfg(unix)]
+    Syslog(UnixDatagram),
+}
+
+#[cfg(unix)]
+fn syslog() -> io::Result<Sink> {
+    let socket = try!(UnixDatagram::unbound());
+    try!(socket.connect("/dev/log"));
+    Ok(Sink::Syslog(socket))
+}
+
+#[cfg(not(unix))]
+fn syslog() -> io::Result<Sink> {
+    Err(io::Error::new(io::ErrorKind::Other, "there is no syslog here"))
+}
+
+impl Sink {
+    fn open(output: &Output) -> io::Result<Sink> {
+        match *output {
+            Output::Stdout => Ok(Sink::Stdout),
+            Output::Stderr => Ok(Sink::Stderr),
+            Output::File(ref file) => Ok(Sink::File(try!(OpenOptions::new().create(true).append(true).open(file)))),
+            Output::Syslog => syslog(),
+        }
+    }
+
+    fn write(&mut self, level: LogLevel, line: &str) -> io::Result<()> {
+        match *self {
+            Sink::Stdout => writeln!(io::stdout(), "{}", line),
+            Sink::Stderr => writeln!(io::stderr(), "{}", line),
+            Sink::File(ref mut file) => writeln!(file, "{}", line),
+            #[cfg(unix)]
+            Sink::Syslog(ref socket) => {
+                // The user facility, with the closest severity
+                let severity = match level {
+                    LogLevel::Error => 3,
+                    LogLevel::Warn => 4,
+                    LogLevel::Info => 6,
+                    LogLevel::Debug | LogLevel::Trace => 7,
+                };
+                let message = format!("<{}>frog_gopher[{}]: {}", 8 + severity, ::std::process::id(), line);
+                socket.send(message.as_bytes()).map(|_| ())
+            },
+        }
+    }
+}
+
+fn format_line(format: Format, now: &time::Tm, thread_name: &str, level: LogLevel, module: &str, message: &str) -> String {
+    match format {
+        Format::Text => format!("{} {} {} {}", now.rfc3339(), thread_name, level, message),
+        Format::Json => {
+            let mut line = BTreeMap::new();
+            line.insert("time".to_string(), now.rfc3339().to_string().to_json());
+            line.insert("thread".to_string(), thread_name.to_json());
+            line.insert("level".to_string(), level.to_string().to_json());
+            line.insert("module".to_string(), module.to_json());
+            line.insert("message".to_string(), message.to_json());
+            Json::Object(line).to_string()
+        },
+    }
+}
+
+struct FrogLogger {
+    levels: Levels,
+    format: Format,
+    sink: Mutex<Sink>,
+}
 
 impl log::Log for FrogLogger {
     fn enabled(&self, metadata: &LogMetadata) -> bool {
-        metadata.level() <= LogLevel::Info
+        metadata.level() <= self.levels.level_for(metadata.target())
     }
 
     fn log(&self, record: &LogRecord) {
         if self.enabled(record.metadata()) {
             let thread = thread::current();
             let thread_name = thread.name().unwrap_or("");
-            let now = time::now_utc();
-            let formatted_now = now.rfc3339();
-            println!("{} {} {} {}", formatted_now, thread_name, record.level(), record.args());
+            let line = format_line(self.format, &time::now_utc(), thread_name, record.level(), record.target(),
+                                   &record.args().to_string());
+            // Nowhere left to complain to
+            let _ = self.sink.lock().unwrap().write(record.level(), &line);
         }
     }
 }
 
-pub fn init() -> Result<(), SetLoggerError> {
+pub fn init(config: &LogConfig) -> io::Result<()> {
+    let sink = try!(Sink::open(&config.output));
+    let logger = FrogLogger {
+        levels: config.levels.clone(),
+        format: config.format,
+        sink: Mutex::new(sink),
+    };
+
     log::set_logger(|max_log_level| {
-        max_log_level.set(LogLevelFilter::Info);
-        Box::new(FrogLogger)
-    })
+        max_log_level.set(logger.levels.max());
+        Box::new(logger)
+    }).map_err(|why| io::Error::new(io::ErrorKind::Other, why))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use log::{LogLevel,LogLevelFilter};
+    use time;
+
+    use super::{format_line,Format,Levels,Output};
+
+
+    #[test]
+    fn modules_get_levels_of_their_own() {
+        let levels: Levels = "warn,tip_source=debug,frog_gopher::the_impl_ya_dummy=error".parse().unwrap();
+        assert_eq!(levels.level_for("frog_gopher::the_impl_ya_dummy::tip_source"), LogLevelFilter::Debug);
+        assert_eq!(levels.level_for("frog_gopher::the_impl_ya_dummy::menu"), LogLevelFilter::Error);
+        assert_eq!(levels.level_for("frog_gopher::gopher_pool"), LogLevelFilter::Warn);
+        // Only whole modules count
+        assert_eq!(levels.level_for("frog_gopher::not_tip_source"), LogLevelFilter::Warn);
+        assert_eq!(levels.max(), LogLevelFilter::Debug);
+
+        assert_eq!("".parse::<Levels>().unwrap(), Levels::default());
+        assert!("frog=loud".parse::<Levels>().is_err());
+        assert_eq!("logs/frog.log".parse::<Output>().unwrap(), Output::File("logs/frog.log".into()));
+    }
+
+    #[test]
+    fn lines_are_text_or_json() {
+        let now = time::at_utc(time::Timespec::new(1500000000, 0));
+        assert_eq!(format_line(Format::Text, &now, "GOPHER_1", LogLevel::Info, "frog_gopher::gopher_pool", "FROG \"HI\""),
+                   "2017-07-14T02:40:00Z GOPHER_1 INFO FROG \"HI\"");
+        assert_eq!(format_line(Format::Json, &now, "GOPHER_1", LogLevel::Info, "frog_gopher::gopher_pool", "FROG \"HI\""),
+                   "{\"level\":\"INFO\",\"message\":\"FROG \\\"HI\\\"\",\"module\":\"frog_gopher::gopher_pool\",\
+                    \"thread\":\"GOPHER_1\",\"time\":\"2017-07-14T02:40:00Z\"}");
+    }
 }
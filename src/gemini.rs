@@ -0,0 +1,179 @@
+// The same gopherhole, for clients that speak Gemini instead. Paths follow gopher URLs,
+// and searches go in the query: /7/TIP/SEARCH?FROG
+
+use std::io;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use futures::{future,Future};
+use tokio::io::{AsyncRead,AsyncWrite};
+use url::Url;
+
+use protocol::{self,Menu,MenuItem,Protocol,Selected,Selector};
+use the_impl_ya_dummy::{Gopher,hang_up};
+use gopher_url::{self,Route};
+use rate_limit::SLOW_DOWN;
+use access_log::{Outcome,Served};
+
+
+// The spec allows a URL of up to 1024 bytes
+const MAX_LINE_LEN: usize = 1024;
+
+fn parse(line: &str) -> Result<Route, String> {
+    let url = match Url::parse(line) {
+        Ok(url) => url,
+        Err(why) => return Err(format!("BAD URL: {}", why)),
+    };
+
+    if url.scheme() != "gemini" {
+        return Err(format!("FROG ONLY SPEAKS GEMINI, NOT {}", url.scheme().to_uppercase()));
+    }
+
+    Ok(gopher_url::route(url.path(), url.query().map(gopher_url::decode)))
+}
+
+// Info lines are plain text, so anything gemtext would read as a link, heading, list, quote or
+// preformatting toggle is pushed over by a space
+fn info(desc: &str) -> String {
+    desc.split('\n')
+        .map(|line| if ["=>", "#", "*", ">", "```"].iter().any(|mark| line.starts_with(mark)) {
+            format!(" {}", line)
+        } else {
+            line.to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\r\n")
+}
+
+fn write_menu(resp: &mut Vec<u8>, menu: &Menu) {
+    resp.extend_from_slice(b"20 text/gemini; charset=utf-8\r\n");
+    for item in menu.items() {
+        let line = match item {
+            MenuItem::Text {ref path, ref desc} => format!("=> {} {}", gopher_url::link('0', path), desc),
+            MenuItem::Directory {ref path, ref desc} => format!("=> {} {}", gopher_url::link('1', path), desc),
+            MenuItem::Search {ref path, ref desc} => format!("=> {} {}", gopher_url::link('7', path), desc),
+            MenuItem::JohnGoerzenUrl {ref url, ref desc} => format!("=> {} {}", url, desc),
+            MenuItem::Info {ref desc} => info(desc),
+            MenuItem::Binary {ref path, ref desc} |
+            MenuItem::Gif {ref path, ref desc} |
+            MenuItem::Image {ref path, ref desc} |
+            MenuItem::Archive {ref path, ref desc} |
+            MenuItem::Sound {ref path, ref desc} => format!("=> {} {}", gopher_url::link(item.item_type(), path), desc),
+            MenuItem::Elsewhere {item_type, ref path, ref desc, ref host, port} =>
+                format!("=> {} {}", gopher_url::elsewhere(host, port, item_type, path), desc),
+        };
+        resp.extend_from_slice(line.as_bytes());
+        resp.extend_from_slice(b"\r\n");
+    }
+}
+
+// Binaries are sent as whatever MIME type was asked for
+fn write(resp: &mut Vec<u8>, selected: &Selected, mime: &str) {
+    match selected {
+        &Selected::Error(ref why) => resp.extend_from_slice(format!("51 {}\r\n", why).as_bytes()),
+        &Selected::Failed(ref why) => resp.extend_from_slice(format!("40 {}\r\n", why).as_bytes()),
+        &Selected::SlowDown => resp.extend_from_slice(format!("44 {}\r\n", SLOW_DOWN).as_bytes()),
+        &Selected::Text(ref text) => {
+            resp.extend_from_slice(b"20 text/plain; charset=utf-8\r\n");
+            resp.extend_from_slice(text.as_bytes());
+        },
+        &Selected::Binary(ref bytes) => {
+            resp.extend_from_slice(format!("20 {}\r\n", mime).as_bytes());
+            resp.extend_from_slice(bytes);
+        },
+        &Selected::ForeverMenu(ref menu) => write_menu(resp, *menu),
+        &Selected::TempMenu(ref menu) => write_menu(resp, &**menu),
+    }
+}
+
+// What to call a binary, going by what the client asked for
+fn mime(item_type: char, selector: &Selector) -> &'static str {
+    match selector {
+        &Selector::Path(ref path) => protocol::binary_mime(item_type, path.val()),
+        &Selector::Empty => protocol::binary_mime(item_type, ""),
+    }
+}
+
+// Finds what the selector leads to and writes it, binaries as the given type
+fn find(gopher: Arc<Gopher>, peer: IpAddr, selector: Selector, item_type: char) -> Box<Future<Item=(Vec<u8>, Served), Error=io::Error> + Send> {
+    Gopher::find_then(gopher, Some(peer), selector, move |_, selector, selected| {
+        let mut resp = Vec::new();
+        write(&mut resp, &selected, mime(item_type, selector));
+        Ok((resp, Served::found(selector, &selected, item_type)))
+    })
+}
+
+fn answer(gopher: Arc<Gopher>, peer: IpAddr, line: &str) -> Box<Future<Item=(Vec<u8>, Served), Error=io::Error> + Send> {
+    let mut resp = Vec::new();
+    let served = match parse(line) {
+        Ok(Route::Menu(selector)) | Ok(Route::Text(selector)) => return find(gopher, peer, selector, '9'),
+        Ok(Route::Binary(item_type, selector)) => return find(gopher, peer, selector, item_type),
+        Ok(Route::Search(ref search)) if search.extra().is_none() => {
+            resp.extend_from_slice(b"10 WHAT ARE YOU LOOKING FOR?\r\n");
+            Served::new(search.val().clone(), '7', Outcome::Served)
+        },
+        Ok(Route::Search(search)) => return find(gopher, peer, Selector::Path(search), '9'),
+        Ok(Route::NotFound(path)) => {
+            resp.extend_from_slice(format!("51 {} NOT FOUND\r\n", path).as_bytes());
+            Served::new(path, '3', Outcome::NotFound)
+        },
+        Err(why) => {
+            resp.extend_from_slice(format!("59 {}\r\n", why).as_bytes());
+            Served::new(line, '3', Outcome::Bad)
+        },
+    };
+    Box::new(future::ok((resp, served)))
+}
+
+// Reads the URL the client wants and answers it the way a gopher would have been
+pub fn respond<S>(gopher: Arc<Gopher>, stream: S, peer: IpAddr) -> Box<Future<Item=Served, Error=io::Error> + Send>
+    where S: AsyncRead + AsyncWrite + Send + 'static {
+    // A URL is one long "selector" as far as the protocol is concerned
+    let reader = gopher.clone();
+    let read = Protocol::new(gopher.ext_addr(), MAX_LINE_LEN).read(stream).map_err(move |why| {
+        reader.metrics().protocol_error(why.name(), &why.to_string());
+        io::Error::from(why)
+    });
+
+    let answered = read.and_then(move |(stream, _, selector)| {
+        let line = match selector {
+            Selector::Path(path) => path.val().clone(),
+            Selector::Empty => String::new(),
+        };
+        answer(gopher, peer, &line).map(move |(resp, served)| (stream, resp, served))
+    });
+
+    Box::new(answered.and_then(|(stream, resp, served)| hang_up(stream, resp, served)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use protocol::{Path,Selector};
+    use gopher_url::Route;
+    use super::{info,parse};
+
+
+    #[test]
+    fn urls_become_routes() {
+        assert_eq!(parse("gemini://frog.tips"), Ok(Route::Menu(Selector::Empty)));
+        assert_eq!(parse("gemini://frog.tips/1/DOCS"), Ok(Route::Menu(Selector::Path(Path::from("/DOCS")))));
+        assert_eq!(parse("gemini://frog.tips/7/TIP/SEARCH"), Ok(Route::Search(Path::from("/TIP/SEARCH"))));
+        assert_eq!(parse("gemini://frog.tips/7/TIP/SEARCH?FROG%20TOY"),
+                   Ok(Route::Search(Path::new("/TIP/SEARCH", Some("FROG TOY")))));
+    }
+
+    #[test]
+    fn info_lines_stay_plain() {
+        assert_eq!(info("FROG."), "FROG.");
+        assert_eq!(info("=> gemini://toad.tips\n# FROG\n* FROG\n> FROG\n```"),
+                   " => gemini://toad.tips\r\n # FROG\r\n * FROG\r\n > FROG\r\n ```");
+        assert_eq!(info("\nFROG => TOAD"), "\r\nFROG => TOAD");
+    }
+
+    #[test]
+    fn only_gemini_urls() {
+        assert!(parse("http://frog.tips/").is_err());
+        assert!(parse("FROG").is_err());
+    }
+}
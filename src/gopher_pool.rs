@@ -0,0 +1,360 @@
+use std::io::{self,Read,Write};
+use std::net::{self,IpAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize,Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration,Instant};
+
+use futures::{future,Async,Future,Poll};
+use tokio::io::{AsyncRead,AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::reactor::Handle;
+use tokio::runtime::{self,Runtime};
+use tokio::timer::Delay;
+use tokio_tls::TlsAcceptor;
+
+use the_impl_ya_dummy::{self,Gopher};
+use gemini;
+use http_gateway;
+use finger;
+use rate_limit::{Admission,SLOW_DOWN};
+use access_log::{AccessLog,Outcome,Served};
+
+
+static TOO_BUSY: &'static str = "FROG IS BUSY. PLEASE TRY AGAIN LATER.";
+
+// How long a gopher may go without saying anything or taking any of the answer
+const PATIENCE_SECS: u64 = 60;
+
+// Gives up on a stream that has gone quiet, however long the whole answer takes
+struct Patient<S> {
+    stream: S,
+    patience: Duration,
+    deadline: Delay,
+}
+
+impl<S> Patient<S> {
+    fn new(stream: S, patience: Duration) -> Patient<S> {
+        Patient {
+            stream: stream,
+            patience: patience,
+            deadline: Delay::new(Instant::now() + patience),
+        }
+    }
+
+    // Anything getting through starts the wait over
+    fn waited<T>(&mut self, res: io::Result<T>) -> io::Result<T> {
+        match res {
+            Ok(done) => {
+                self.deadline.reset(Instant::now() + self.patience);
+                Ok(done)
+            },
+            Err(why) => {
+                if why.kind() != io::ErrorKind::WouldBlock {
+                    return Err(why);
+                }
+                match self.deadline.poll() {
+                    Ok(Async::Ready(())) => Err(io::Error::new(io::ErrorKind::TimedOut, "the gopher went quiet")),
+                    Ok(Async::NotReady) => Err(why),
+                    Err(timer) => Err(io::Error::new(io::ErrorKind::Other, timer)),
+                }
+            },
+        }
+    }
+}
+
+impl<S: Read> Read for Patient<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let res = self.stream.read(buf);
+        self.waited(res)
+    }
+}
+
+impl<S: Write> Write for Patient<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let res = self.stream.write(buf);
+        self.waited(res)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let res = self.stream.flush();
+        self.waited(res)
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for Patient<S> {}
+
+impl<S: AsyncWrite> AsyncWrite for Patient<S> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.stream.shutdown()
+    }
+}
+
+// Which protocol a listener speaks
+#[derive(Clone,Copy,Debug)]
+pub enum Frontend {
+    Gopher,
+    Gemini,
+    Http,
+    Finger,
+}
+
+impl Frontend {
+    // What the access log calls it
+    pub fn name(&self, tls: bool) -> &'static str {
+        match (*self, tls) {
+            (Frontend::Gopher, false) => "gopher",
+            (Frontend::Gopher, true) => "gopher-tls",
+            (Frontend::Gemini, _) => "gemini",
+            (Frontend::Http, _) => "http",
+            (Frontend::Finger, _) => "finger",
+        }
+    }
+
+    fn respond<S>(self, gopher: Arc<Gopher>, stream: S, peer: IpAddr) -> Box<Future<Item=Served, Error=io::Error> + Send>
+        where S: AsyncRead + AsyncWrite + Send + 'static {
+        match self {
+            Frontend::Gopher => Gopher::respond(gopher, stream, Some(peer)),
+            Frontend::Gemini => gemini::respond(gopher, stream, peer),
+            Frontend::Http => http_gateway::respond(gopher, stream, peer),
+            Frontend::Finger => finger::respond(gopher, stream, peer),
+        }
+    }
+}
+
+// Gophers are answered on a runtime, so one that takes its time costs a connection and not a thread.
+// Finding things to send them can block, which happens on a fixed number of workers. Frog is busy
+// once too many finds are waiting for a worker, or there are more connections than it can hold.
+pub struct GopherPool {
+    gopher: Arc<Gopher>,
+    runtime: Runtime,
+    max_waiting: usize,
+    // Picked up and not yet answered
+    open: Arc<AtomicUsize>,
+    max_connections: usize,
+    // Nobody is told about requests unless this is set
+    access_log: Option<Arc<AccessLog>>,
+}
+
+impl GopherPool {
+    pub fn new(gopher: Arc<Gopher>, size: usize, max_waiting: usize, max_connections: usize) -> io::Result<GopherPool> {
+        let runtime = try!(runtime::Builder::new()
+            .blocking_threads(size)
+            .name_prefix("GOPHER_")
+            .build());
+
+        info!("FROG HAS DUG {} BURROWS WITH ROOM FOR {} GOPHERS TO WAIT AND {} TO HANG AROUND.", size, max_waiting, max_connections);
+
+        Ok(GopherPool {
+            gopher: gopher,
+            runtime: runtime,
+            max_waiting: max_waiting,
+            open: Arc::new(AtomicUsize::new(0)),
+            max_connections: max_connections,
+            access_log: None,
+        })
+    }
+
+    pub fn set_access_log(&mut self, access_log: AccessLog) {
+        self.access_log = Some(Arc::new(access_log));
+    }
+
+    // Finds waiting for a worker to come free
+    pub fn waiting(&self) -> usize {
+        the_impl_ya_dummy::waiting_for_a_burrow()
+    }
+
+    pub fn open(&self) -> usize {
+        self.open.load(Ordering::SeqCst)
+    }
+
+    // Hands the stream to the runtime, or turns it away if there are too many gophers around
+    pub fn dispatch(&self, stream: net::TcpStream) -> io::Result<()> {
+        self.dispatch_to(stream, Frontend::Gopher, None)
+    }
+
+    // The same, for gophers who want to talk over TLS first
+    pub fn dispatch_tls(&self, stream: net::TcpStream, tls: &TlsAcceptor) -> io::Result<()> {
+        self.dispatch_to(stream, Frontend::Gopher, Some(tls))
+    }
+
+    // Only for gophers that are asked to slow down or told frog is busy
+    fn turn_away(&self, stream: net::TcpStream, frontend: Frontend, tls: Option<&TlsAcceptor>, outcome: Outcome) -> io::Result<()> {
+        let why = match outcome {
+            Outcome::SlowDown => SLOW_DOWN,
+            _ => TOO_BUSY,
+        };
+        if let (Some(ref access_log), Ok(addr)) = (self.access_log.as_ref(), stream.peer_addr()) {
+            access_log.record(Some(addr.ip()), frontend.name(tls.is_some()), &Served::nothing(outcome), Duration::from_secs(0));
+        }
+
+        match (frontend, tls) {
+            (Frontend::Gopher, None) => self.gopher.turn_away(stream, why),
+            (Frontend::Http, None) => http_gateway::turn_away(stream, why),
+            (Frontend::Finger, None) => finger::turn_away(stream, why),
+            // Saying no in the clear would only confuse it
+            (_, _) => Ok(()),
+        }
+    }
+
+    pub fn dispatch_to(&self, stream: net::TcpStream, frontend: Frontend, tls: Option<&TlsAcceptor>) -> io::Result<()> {
+        let addr = try!(stream.peer_addr());
+        let arrived = Instant::now();
+
+        match self.gopher.admit(&addr.ip()) {
+            Admission::Welcome => {},
+            Admission::SlowDown => return self.turn_away(stream, frontend, tls, Outcome::SlowDown),
+            Admission::Refused => {
+                if let Some(ref access_log) = self.access_log {
+                    access_log.record(Some(addr.ip()), frontend.name(tls.is_some()), &Served::nothing(Outcome::Refused), Duration::from_secs(0));
+                }
+                return Ok(());
+            },
+        }
+
+        if self.waiting() >= self.max_waiting {
+            warn!("GOPHER {} WAS TURNED AWAY ({} OF {} WAITING).", addr, self.waiting(), self.max_waiting);
+            return self.turn_away(stream, frontend, tls, Outcome::Busy);
+        }
+        if self.open.fetch_add(1, Ordering::SeqCst) >= self.max_connections {
+            self.open.fetch_sub(1, Ordering::SeqCst);
+            warn!("GOPHER {} WAS TURNED AWAY ({} OF {} AROUND).", addr, self.open(), self.max_connections);
+            return self.turn_away(stream, frontend, tls, Outcome::Busy);
+        }
+
+        info!("GOPHER {} HAS BEEN LET IN ({} WAITING, {} OF {} AROUND).", addr, self.waiting(), self.open(), self.max_connections);
+
+        let gopher = self.gopher.clone();
+        let name = frontend.name(tls.is_some());
+        let access_log = self.access_log.clone();
+        let tls = tls.cloned();
+        let open = self.open.clone();
+
+        self.runtime.executor().spawn(future::lazy(move || {
+            info!("A GOPHER HAS POPPED OUT OF ITS BURROW AT {}.", addr);
+            let popped_out = Instant::now();
+            let counter = gopher.clone();
+            counter.metrics().connection_opened(name);
+
+            let answered = future::result(TcpStream::from_std(stream, &Handle::default()))
+                .map(|stream| Patient::new(stream, Duration::from_secs(PATIENCE_SECS)))
+                .and_then(move |stream| -> Box<Future<Item=Served, Error=io::Error> + Send> {
+                    match tls {
+                        None => frontend.respond(gopher, stream, addr.ip()),
+                        Some(tls) => Box::new(tls.accept(stream)
+                                                 .map_err(|why| io::Error::new(io::ErrorKind::InvalidData, why))
+                                                 .and_then(move |stream| frontend.respond(gopher, stream, addr.ip()))),
+                    }
+                });
+
+            answered.then(move |res| {
+                open.fetch_sub(1, Ordering::SeqCst);
+                counter.metrics().connection_closed(name);
+                counter.metrics().responded(name, popped_out.elapsed());
+                let served = match res {
+                    Ok(served) => {
+                        info!("A GOPHER HAS RETREATED INTO ITS BURROW ON GOOD TERMS. GOODBYE GOPHER.");
+                        served
+                    },
+                    Err(why) => {
+                        error!("A GOPHER HAS RETREATED INTO ITS BURROW ON BAD TERMS: {}", why);
+                        counter.metrics().remember_error(&format!("{} GOPHER {}: {}", name.to_uppercase(), addr, why));
+                        Served::nothing(Outcome::Failed)
+                    },
+                };
+                if let Some(access_log) = access_log {
+                    access_log.record(Some(addr.ip()), name, &served, arrived.elapsed());
+                }
+                Ok(())
+            })
+        }));
+
+        Ok(())
+    }
+
+    // The gopher's metrics, and how crowded it is here
+    pub fn render_metrics(&self) -> String {
+        let mut metrics = self.gopher.metrics().render();
+        metrics.push_str("# HELP frog_gophers_open Gophers let in and not yet answered.\n");
+        metrics.push_str("# TYPE frog_gophers_open gauge\n");
+        metrics.push_str(&format!("frog_gophers_open {}\n", self.open()));
+        metrics.push_str("# HELP frog_gophers_max_open How many gophers may be around before frog is busy.\n");
+        metrics.push_str("# TYPE frog_gophers_max_open gauge\n");
+        metrics.push_str(&format!("frog_gophers_max_open {}\n", self.max_connections));
+        metrics.push_str("# HELP frog_gophers_waiting Finds waiting for a worker to come free.\n");
+        metrics.push_str("# TYPE frog_gophers_waiting gauge\n");
+        metrics.push_str(&format!("frog_gophers_waiting {}\n", self.waiting()));
+        metrics.push_str("# HELP frog_gophers_max_waiting How many finds may wait before frog is busy.\n");
+        metrics.push_str("# TYPE frog_gophers_max_waiting gauge\n");
+        metrics.push_str(&format!("frog_gophers_max_waiting {}\n", self.max_waiting));
+        metrics
+    }
+
+    // Lets everyone in line finish, then waits for the workers to stop
+    pub fn join(self) {
+        let _ = self.runtime.shutdown_on_idle().wait();
+    }
+
+    // Like join, but gives up after a while. True if everyone finished in time.
+    pub fn join_within(self, grace: Duration) -> bool {
+        let (done, finished) = mpsc::channel();
+        let joiner = thread::Builder::new()
+            .name("GOPHER_JOINER".to_string())
+            .spawn(move || {
+                self.join();
+                let _ = done.send(());
+            });
+
+        match joiner {
+            Ok(_) => finished.recv_timeout(grace).is_ok(),
+            Err(_) => false,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self,Write};
+    use std::net;
+    use std::thread;
+    use std::time::Duration;
+
+    use tokio::net::TcpStream;
+    use tokio::reactor::Handle;
+    use tokio::runtime::Runtime;
+    use super::Patient;
+
+
+    fn accept_patiently(listener: &net::TcpListener, patience: Duration) -> Patient<TcpStream> {
+        let stream = listener.accept().unwrap().0;
+        Patient::new(TcpStream::from_std(stream, &Handle::default()).unwrap(), patience)
+    }
+
+    #[test]
+    fn only_quiet_gophers_run_out_of_patience() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut runtime = Runtime::new().unwrap();
+
+        // Takes longer than frog waits for anything, but never goes quiet for that long
+        let chatty = thread::spawn(move || {
+            let mut stream = net::TcpStream::connect(addr).unwrap();
+            for _ in 0..6 {
+                stream.write_all(b"F").unwrap();
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+        let stream = accept_patiently(&listener, Duration::from_millis(200));
+        assert_eq!(runtime.block_on(::tokio::io::read_to_end(stream, Vec::new())).unwrap().1, b"FFFFFF");
+        chatty.join().unwrap();
+
+        let _quiet = net::TcpStream::connect(addr).unwrap();
+        let stream = accept_patiently(&listener, Duration::from_millis(100));
+        match runtime.block_on(::tokio::io::read_to_end(stream, Vec::new())) {
+            Err(why) => assert_eq!(why.kind(), io::ErrorKind::TimedOut),
+            Ok(_) => panic!("FROG WAITED FOREVER"),
+        }
+    }
+}
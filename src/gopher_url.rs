@@ -0,0 +1,90 @@
+// Paths the way gopher URLs write them, for frontends that speak in URLs.
+//
+// The first character after the slash is the item type, so /1/DOCS is a menu,
+// /0/DOCS/FROG_MODELS is text, /7/TIP/SEARCH is a search and /I/PHOTOS/FROG.PNG is an image.
+
+use url::percent_encoding::{percent_decode,utf8_percent_encode,DEFAULT_ENCODE_SET};
+
+use protocol::{Path,Selector};
+
+
+#[derive(Debug,Eq,PartialEq)]
+pub enum Route {
+    Menu(Selector),
+    Text(Selector),
+    // Any of the binary types, which frontends need for the MIME type
+    Binary(char, Selector),
+    // Whatever to search for is the extra, if the client has said yet
+    Search(Path),
+    NotFound(String),
+}
+
+pub fn decode(encoded: &str) -> String {
+    percent_decode(encoded.as_bytes()).decode_utf8_lossy().into_owned()
+}
+
+// Takes the path still percent-encoded and the query already decoded
+pub fn route(encoded_path: &str, query: Option<String>) -> Route {
+    let path = decode(encoded_path);
+    let mut chars = path.trim_left_matches('/').chars();
+    let item_type = chars.next();
+    let selector = match chars.as_str() {
+        "" | "/" => Selector::Empty,
+        selector => Selector::Path(Path::from(selector)),
+    };
+
+    match (item_type, selector) {
+        (None, _) => Route::Menu(Selector::Empty),
+        (Some('1'), selector) => Route::Menu(selector),
+        (Some('0'), selector @ Selector::Path(_)) => Route::Text(selector),
+        (Some('7'), Selector::Path(search)) => Route::Search(Path::new(search.val().clone(), query)),
+        (Some(item_type @ '9'), selector @ Selector::Path(_)) |
+        (Some(item_type @ 'g'), selector @ Selector::Path(_)) |
+        (Some(item_type @ 'I'), selector @ Selector::Path(_)) |
+        (Some(item_type @ '5'), selector @ Selector::Path(_)) |
+        (Some(item_type @ 's'), selector @ Selector::Path(_)) => Route::Binary(item_type, selector),
+        _ => Route::NotFound(path),
+    }
+}
+
+pub fn link(item_type: char, path: &Path) -> String {
+    format!("/{}{}", item_type, utf8_percent_encode(path.val(), DEFAULT_ENCODE_SET))
+}
+
+// The same, for something on another gopher server
+pub fn elsewhere(host: &str, port: u16, item_type: char, path: &Path) -> String {
+    format!("gopher://{}:{}{}", host, port, link(item_type, path))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use protocol::{Path,Selector};
+    use super::{route,link,elsewhere,Route};
+
+
+    #[test]
+    fn paths_are_gopher_urls() {
+        assert_eq!(route("", None), Route::Menu(Selector::Empty));
+        assert_eq!(route("/", None), Route::Menu(Selector::Empty));
+        assert_eq!(route("/1/DOCS", None), Route::Menu(Selector::Path(Path::from("/DOCS"))));
+        assert_eq!(route("/0/DOCS/FROG_MODELS", None), Route::Text(Selector::Path(Path::from("/DOCS/FROG_MODELS"))));
+        assert_eq!(route("/0/JOB%20OPENINGS", None), Route::Text(Selector::Path(Path::from("/JOB OPENINGS"))));
+        assert_eq!(route("/I/PHOTOS/FROG.PNG", None), Route::Binary('I', Selector::Path(Path::from("/PHOTOS/FROG.PNG"))));
+        assert_eq!(route("/9/", None), Route::NotFound("/9/".to_string()));
+        assert_eq!(route("/X/FIRMWARE", None), Route::NotFound("/X/FIRMWARE".to_string()));
+    }
+
+    #[test]
+    fn searches_keep_their_terms() {
+        assert_eq!(route("/7/TIP/SEARCH", None), Route::Search(Path::from("/TIP/SEARCH")));
+        assert_eq!(route("/7/TIP/SEARCH", Some("FROG".to_string())), Route::Search(Path::new("/TIP/SEARCH", Some("FROG"))));
+    }
+
+    #[test]
+    fn links_are_encoded() {
+        assert_eq!(link('0', &Path::from("/JOB OPENINGS")), "/0/JOB%20OPENINGS");
+        assert_eq!(link('1', &Path::from("/DOCS")), "/1/DOCS");
+        assert_eq!(elsewhere("gopher.floodgap.com", 70, '1', &Path::from("/world")), "gopher://gopher.floodgap.com:70/1/world");
+    }
+}
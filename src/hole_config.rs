@@ -0,0 +1,632 @@
+use std::collections::HashSet;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Path,PathBuf};
+use std::time::Duration;
+
+use hyper::Url;
+use hyper::error::ParseError;
+use toml;
+
+
+static DEFAULT: &'static str = include_str!("../frog_gopher.toml");
+
+pub static README: &'static str = include_str!("../txt/README");
+static FROG_MODELS: &'static str = include_str!("../txt/FROG_MODELS");
+static FIRMWARE_V2: &'static str = include_str!("../txt/FIRMWARE_V2");
+static JOB_OPENINGS: &'static str = include_str!("../txt/JOB_OPENINGS");
+static EVACUATION_PROCEDURE: &'static str = include_str!("../txt/EVACUATION_PROCEDURE");
+
+// Every page of the manual, named the way they are in txt/USER_MANUAL
+static USER_MANUAL: &'static [(&'static str, &'static str)] = &[
+    ("APPENDIX_A", include_str!("../txt/USER_MANUAL/APPENDIX_A")),
+    ("APPENDIX_B", include_str!("../txt/USER_MANUAL/APPENDIX_B")),
+    ("APPENDIX_C", include_str!("../txt/USER_MANUAL/APPENDIX_C")),
+    ("CHAPTER_01", include_str!("../txt/USER_MANUAL/CHAPTER_01")),
+    ("CHAPTER_02", include_str!("../txt/USER_MANUAL/CHAPTER_02")),
+    ("CHAPTER_03", include_str!("../txt/USER_MANUAL/CHAPTER_03")),
+    ("CHAPTER_04", include_str!("../txt/USER_MANUAL/CHAPTER_04")),
+    ("CHAPTER_05", include_str!("../txt/USER_MANUAL/CHAPTER_05")),
+    ("CHAPTER_06", include_str!("../txt/USER_MANUAL/CHAPTER_06")),
+    ("FOREWORD", include_str!("../txt/USER_MANUAL/FOREWORD")),
+];
+
+// Where and how often tips are fetched unless told otherwise
+pub static DEFAULT_TIP_API_URL: &'static str = "https://frog.tips/api/2";
+static DEFAULT_TIP_REFRESH_SECS: u64 = 300;
+
+// These sources pick their own selectors
+static GENUINE_FROG_PATH: &'static str = "/GENUINEFROG";
+static TIPS_PATH: &'static str = "/TIP/SEARCH";
+// Where tips themselves are found, one per selector
+static TIPS_ROOT: &'static str = "/TIP";
+
+fn builtin(name: &str) -> Option<&'static str> {
+    match name {
+        "README" => Some(README),
+        "FROG_MODELS" => Some(FROG_MODELS),
+        "FIRMWARE_V2" => Some(FIRMWARE_V2),
+        "JOB_OPENINGS" => Some(JOB_OPENINGS),
+        "EVACUATION_PROCEDURE" => Some(EVACUATION_PROCEDURE),
+        _ => None,
+    }
+}
+
+#[derive(Clone,Debug)]
+pub enum SourceConfig {
+    Info {text: String},
+    Url {url: Url, desc: String},
+    Text {path: String, desc: String, text: String},
+    Binary {path: String, desc: String, file: PathBuf},
+    Bogus {path: String, desc: String},
+    Files {path: String, desc: String, dir: PathBuf},
+    // Pages are named and sorted by file name
+    Manual {path: String, desc: String, pages: Vec<(String, String)>},
+    Menu {path: String, desc: String, sources: Vec<SourceConfig>},
+    // Checks serials against the registry built into the server unless given another
+    GenuineFrog {registry: Option<PathBuf>},
+    Tips {api_url: String, dump: Option<PathBuf>, refresh: Duration},
+}
+
+#[derive(Clone,Debug)]
+pub struct HoleConfig {
+    pub sources: Vec<SourceConfig>,
+    // Old selectors, and where whatever used to be there lives now
+    pub aliases: Vec<(String, String)>,
+}
+
+// What the file says, before we've checked that it makes any sense
+
+#[derive(RustcDecodable)]
+struct RawHole {
+    source: Option<Vec<RawSource>>,
+    alias: Option<Vec<RawAlias>>,
+}
+
+#[derive(RustcDecodable)]
+struct RawAlias {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(RustcDecodable)]
+struct RawSource {
+    kind: String,
+    path: Option<String>,
+    desc: Option<String>,
+    url: Option<String>,
+    text: Option<String>,
+    file: Option<String>,
+    builtin: Option<String>,
+    dir: Option<String>,
+    api: Option<String>,
+    dump: Option<String>,
+    refresh: Option<u64>,
+    registry: Option<String>,
+    source: Option<Vec<RawSource>>,
+}
+
+struct Resolver<'a> {
+    base: &'a Path,
+    selectors: HashSet<String>,
+    // The menu whose sources are being resolved, if any. Nothing outside it could be reached.
+    menu: Option<String>,
+}
+
+impl<'a> Resolver<'a> {
+    fn claim(&mut self, path: &str) -> Result<(), HoleConfigError> {
+        let normalized = path.trim_right_matches('/').to_string();
+        if let Some(ref menu) = self.menu {
+            let prefix = menu.trim_right_matches('/');
+            let inside = normalized.starts_with(prefix) && normalized[prefix.len()..].starts_with('/');
+            if !inside {
+                return Err(HoleConfigError::OutsideMenu(path.to_string(), menu.clone()));
+            }
+        }
+        if self.selectors.insert(normalized) {
+            Ok(())
+        } else {
+            Err(HoleConfigError::DuplicateSelector(path.to_string()))
+        }
+    }
+
+    fn require<T: Clone>(kind: &str, field: &'static str, val: &Option<T>) -> Result<T, HoleConfigError> {
+        val.clone().ok_or(HoleConfigError::MissingField(kind.to_string(), field))
+    }
+
+    fn content(&self, raw: &RawSource) -> Result<String, HoleConfigError> {
+        match (&raw.text, &raw.file, &raw.builtin) {
+            (&Some(ref text), _, _) => Ok(text.clone()),
+            (_, &Some(ref file), _) => {
+                let file = self.base.join(file);
+                let mut text = String::new();
+                try!(File::open(&file)
+                          .and_then(|mut f| f.read_to_string(&mut text))
+                          .map_err(|err| HoleConfigError::Io(file.clone(), err)));
+                Ok(text)
+            },
+            (_, _, &Some(ref name)) => {
+                builtin(name).map(|text| text.to_string())
+                             .ok_or(HoleConfigError::UnknownBuiltin(name.clone()))
+            },
+            _ => Err(HoleConfigError::MissingField(raw.kind.clone(), "text")),
+        }
+    }
+
+    fn pages(&self, raw: &RawSource) -> Result<Vec<(String, String)>, HoleConfigError> {
+        let dir = match raw.dir {
+            Some(ref dir) => self.base.join(dir),
+            None => return Ok(USER_MANUAL.iter().map(|&(name, text)| (name.to_string(), text.to_string())).collect()),
+        };
+
+        let mut pages = vec![];
+        let entries = try!(fs::read_dir(&dir).map_err(|err| HoleConfigError::Io(dir.clone(), err)));
+        for entry in entries {
+            let file = try!(entry.map_err(|err| HoleConfigError::Io(dir.clone(), err))).path();
+            let name = match file.file_name().and_then(|name| name.to_str()) {
+                Some(name) if !name.starts_with('.') && file.is_file() => name.to_string(),
+                _ => continue,
+            };
+            // The manual searches itself at SEARCH, so a page there could never be read
+            if name == "SEARCH" {
+                return Err(HoleConfigError::ReservedPage(file));
+            }
+
+            let mut text = String::new();
+            try!(File::open(&file)
+                      .and_then(|mut f| f.read_to_string(&mut text))
+                      .map_err(|err| HoleConfigError::Io(file.clone(), err)));
+            pages.push((name, text));
+        }
+        pages.sort();
+        Ok(pages)
+    }
+
+    fn source(&mut self, raw: &RawSource) -> Result<SourceConfig, HoleConfigError> {
+        let kind = raw.kind.as_str();
+        let source = match kind {
+            "info" => SourceConfig::Info {
+                text: try!(self.content(raw)),
+            },
+            "url" => {
+                let url = try!(Resolver::require(kind, "url", &raw.url));
+                SourceConfig::Url {
+                    url: try!(Url::parse(&url).map_err(|err| HoleConfigError::BadUrl(url.clone(), err))),
+                    desc: try!(Resolver::require(kind, "desc", &raw.desc)),
+                }
+            },
+            "text" => SourceConfig::Text {
+                path: try!(Resolver::require(kind, "path", &raw.path)),
+                desc: try!(Resolver::require(kind, "desc", &raw.desc)),
+                text: try!(self.content(raw)),
+            },
+            "binary" => {
+                let file = self.base.join(try!(Resolver::require(kind, "file", &raw.file)));
+                // Read when asked for, but make sure it's there now
+                try!(File::open(&file).map_err(|err| HoleConfigError::Io(file.clone(), err)));
+                SourceConfig::Binary {
+                    path: try!(Resolver::require(kind, "path", &raw.path)),
+                    desc: try!(Resolver::require(kind, "desc", &raw.desc)),
+                    file: file,
+                }
+            },
+            "bogus" => SourceConfig::Bogus {
+                path: try!(Resolver::require(kind, "path", &raw.path)),
+                desc: try!(Resolver::require(kind, "desc", &raw.desc)),
+            },
+            "files" => SourceConfig::Files {
+                path: try!(Resolver::require(kind, "path", &raw.path)),
+                desc: try!(Resolver::require(kind, "desc", &raw.desc)),
+                dir: self.base.join(try!(Resolver::require(kind, "dir", &raw.dir))),
+            },
+            "menu" => {
+                let path = try!(Resolver::require(kind, "path", &raw.path));
+                let outer = self.menu.replace(path.clone());
+                let sources = self.sources(&raw.source);
+                self.menu = outer;
+                SourceConfig::Menu {
+                    path: path,
+                    desc: try!(Resolver::require(kind, "desc", &raw.desc)),
+                    sources: try!(sources),
+                }
+            },
+            "manual" => SourceConfig::Manual {
+                path: try!(Resolver::require(kind, "path", &raw.path)),
+                desc: try!(Resolver::require(kind, "desc", &raw.desc)),
+                pages: try!(self.pages(raw)),
+            },
+            "genuine_frog" => {
+                let registry = raw.registry.as_ref().map(|registry| self.base.join(registry));
+                if let Some(ref registry) = registry {
+                    // Read whenever a frog is checked, but make sure it's there now
+                    try!(File::open(registry).map_err(|err| HoleConfigError::Io(registry.clone(), err)));
+                }
+                SourceConfig::GenuineFrog {
+                    registry: registry,
+                }
+            },
+            "tips" => {
+                // Refreshing without a pause would never leave frog.tips alone
+                let refresh = raw.refresh.unwrap_or(DEFAULT_TIP_REFRESH_SECS);
+                if refresh == 0 {
+                    return Err(HoleConfigError::ZeroRefresh);
+                }
+                SourceConfig::Tips {
+                    api_url: raw.api.clone().unwrap_or(DEFAULT_TIP_API_URL.to_string()),
+                    dump: raw.dump.as_ref().map(|dump| self.base.join(dump)),
+                    refresh: Duration::from_secs(refresh),
+                }
+            },
+            _ => return Err(HoleConfigError::UnknownKind(kind.to_string())),
+        };
+
+        match source {
+            SourceConfig::Text {ref path, ..} |
+            SourceConfig::Binary {ref path, ..} |
+            SourceConfig::Bogus {ref path, ..} |
+            SourceConfig::Files {ref path, ..} |
+            SourceConfig::Manual {ref path, ..} |
+            SourceConfig::Menu {ref path, ..} => try!(self.claim(path)),
+            SourceConfig::GenuineFrog {..} => try!(self.claim(GENUINE_FROG_PATH)),
+            SourceConfig::Tips {..} => try!(self.claim(TIPS_PATH)),
+            SourceConfig::Info {..} | SourceConfig::Url {..} => {},
+        };
+
+        Ok(source)
+    }
+
+    fn sources(&mut self, raw: &Option<Vec<RawSource>>) -> Result<Vec<SourceConfig>, HoleConfigError> {
+        let mut sources = vec![];
+        if let &Some(ref raw) = raw {
+            for source in raw {
+                sources.push(try!(self.source(source)));
+            }
+        }
+        Ok(sources)
+    }
+
+    // Run once every source has claimed its selector, so aliases can only point at those
+    fn aliases(&mut self, raw: &Option<Vec<RawAlias>>) -> Result<Vec<(String, String)>, HoleConfigError> {
+        let mut aliases = vec![];
+        if let &Some(ref raw) = raw {
+            for alias in raw {
+                let from = try!(Resolver::require("alias", "from", &alias.from));
+                let to = try!(Resolver::require("alias", "to", &alias.to));
+                if !self.selectors.contains(to.trim_right_matches('/')) {
+                    return Err(HoleConfigError::UnknownAliasTarget(to));
+                }
+                aliases.push((from, to));
+            }
+        }
+        for &(ref from, _) in &aliases {
+            try!(self.claim(from));
+        }
+        Ok(aliases)
+    }
+}
+
+impl HoleConfig {
+    // The hole as it has always been, built right into the server
+    pub fn builtin() -> HoleConfig {
+        HoleConfig::parse(DEFAULT, Path::new(".")).unwrap()
+    }
+
+    // Only tips fetched from the API need a key
+    pub fn needs_api_key(&self) -> bool {
+        fn any_need(sources: &Vec<SourceConfig>) -> bool {
+            sources.iter().any(|source| match source {
+                &SourceConfig::Tips {dump: None, ..} => true,
+                &SourceConfig::Menu {ref sources, ..} => any_need(sources),
+                _ => false,
+            })
+        }
+
+        any_need(&self.sources)
+    }
+
+    // Whether anything in the hole is found at or under the selector, the main menu included
+    pub fn claims(&self, selector: &str) -> bool {
+        let selector = selector.trim_right_matches('/');
+        let under = |path: &str| {
+            let path = path.trim_right_matches('/');
+            selector.starts_with(path) && (selector.len() == path.len() || selector[path.len()..].starts_with('/'))
+        };
+        fn any_claim<F: Fn(&str) -> bool>(sources: &Vec<SourceConfig>, under: &F) -> bool {
+            sources.iter().any(|source| match source {
+                &SourceConfig::Text {ref path, ..} |
+                &SourceConfig::Binary {ref path, ..} |
+                &SourceConfig::Bogus {ref path, ..} |
+                &SourceConfig::Files {ref path, ..} |
+                &SourceConfig::Manual {ref path, ..} => under(path),
+                &SourceConfig::Menu {ref path, ref sources, ..} => under(path) || any_claim(sources, under),
+                &SourceConfig::GenuineFrog {..} => under(GENUINE_FROG_PATH),
+                &SourceConfig::Tips {..} => under(TIPS_ROOT),
+                &SourceConfig::Info {..} | &SourceConfig::Url {..} => false,
+            })
+        }
+
+        selector.is_empty() || any_claim(&self.sources, &under) || self.aliases.iter().any(|&(ref from, _)| under(from))
+    }
+
+    pub fn load<P: AsRef<Path>>(file: P) -> Result<HoleConfig, HoleConfigError> {
+        let file = file.as_ref();
+        let mut text = String::new();
+        try!(File::open(file)
+                  .and_then(|mut f| f.read_to_string(&mut text))
+                  .map_err(|err| HoleConfigError::Io(file.to_path_buf(), err)));
+
+        let base = file.parent().unwrap_or(Path::new("."));
+        HoleConfig::parse(&text, base)
+    }
+
+    pub fn parse(text: &str, base: &Path) -> Result<HoleConfig, HoleConfigError> {
+        let mut parser = toml::Parser::new(text);
+        let table = match parser.parse() {
+            Some(table) => table,
+            None => {
+                let why = parser.errors.iter()
+                                       .map(|err| {
+                                           let (line, col) = parser.to_linecol(err.lo);
+                                           format!("LINE {}, COLUMN {}: {}", line + 1, col + 1, err.desc)
+                                       })
+                                       .collect::<Vec<String>>()
+                                       .join("; ");
+                return Err(HoleConfigError::Parse(why));
+            },
+        };
+
+        let raw: RawHole = try!(::rustc_serialize::Decodable::decode(&mut toml::Decoder::new(toml::Value::Table(table))));
+
+        let mut resolver = Resolver {
+            base: base,
+            selectors: HashSet::new(),
+            menu: None,
+        };
+
+        let sources = try!(resolver.sources(&raw.source));
+        Ok(HoleConfig {
+            sources: sources,
+            aliases: try!(resolver.aliases(&raw.alias)),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum HoleConfigError {
+    Io(PathBuf, io::Error),
+    Parse(String),
+    Decode(toml::DecodeError),
+    UnknownKind(String),
+    UnknownBuiltin(String),
+    MissingField(String, &'static str),
+    BadUrl(String, ParseError),
+    DuplicateSelector(String),
+    UnknownAliasTarget(String),
+    ZeroRefresh,
+    // The path, and the menu it was listed in
+    OutsideMenu(String, String),
+    ReservedPage(PathBuf),
+}
+
+impl fmt::Display for HoleConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HoleConfigError::Io(ref file, ref err) => write!(f, "Could not read {}: {}", file.display(), err),
+            HoleConfigError::Parse(ref why) => write!(f, "Could not parse config: {}", why),
+            HoleConfigError::Decode(ref err) => write!(f, "Config has the wrong shape: {}", err),
+            HoleConfigError::UnknownKind(ref kind) => write!(f, "Unknown source kind '{}'", kind),
+            HoleConfigError::UnknownBuiltin(ref name) => write!(f, "Unknown builtin text '{}'", name),
+            HoleConfigError::MissingField(ref kind, field) => write!(f, "A '{}' source needs a '{}'", kind, field),
+            HoleConfigError::BadUrl(ref url, ref err) => write!(f, "Bad URL '{}': {}", url, err),
+            HoleConfigError::DuplicateSelector(ref path) => write!(f, "Selector '{}' is used more than once", path),
+            HoleConfigError::UnknownAliasTarget(ref path) => write!(f, "Nothing is at '{}' for an alias to point to", path),
+            HoleConfigError::ZeroRefresh => write!(f, "A 'tips' source needs a 'refresh' of at least 1 second"),
+            HoleConfigError::OutsideMenu(ref path, ref menu) => write!(f, "Selector '{}' is listed in menu '{}' but is not under it", path, menu),
+            HoleConfigError::ReservedPage(ref file) => write!(f, "Manual page {} is named like the manual's search", file.display()),
+        }
+    }
+}
+
+impl error::Error for HoleConfigError {
+    fn description(&self) -> &str {
+        match *self {
+            HoleConfigError::Io(_, ref err) => err.description(),
+            HoleConfigError::Parse(_) => "The config is not valid TOML.",
+            HoleConfigError::Decode(ref err) => err.description(),
+            HoleConfigError::UnknownKind(_) => "Unknown source kind.",
+            HoleConfigError::UnknownBuiltin(_) => "Unknown builtin text.",
+            HoleConfigError::MissingField(_, _) => "A source is missing a field.",
+            HoleConfigError::BadUrl(_, ref err) => err.description(),
+            HoleConfigError::DuplicateSelector(_) => "A selector is used more than once.",
+            HoleConfigError::UnknownAliasTarget(_) => "An alias points to nothing.",
+            HoleConfigError::ZeroRefresh => "Tips can't be refreshed every 0 seconds.",
+            HoleConfigError::OutsideMenu(_, _) => "A menu lists a selector that is not under it.",
+            HoleConfigError::ReservedPage(_) => "A manual page is named SEARCH.",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            HoleConfigError::Io(_, ref err) => Some(err),
+            HoleConfigError::Decode(ref err) => Some(err),
+            HoleConfigError::BadUrl(_, ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<toml::DecodeError> for HoleConfigError {
+    fn from(err: toml::DecodeError) -> HoleConfigError {
+        HoleConfigError::Decode(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self,File};
+    use std::io::Write;
+    use std::path::Path;
+
+    use super::{HoleConfig,HoleConfigError,SourceConfig};
+
+
+    fn parse(text: &str) -> Result<HoleConfig, HoleConfigError> {
+        HoleConfig::parse(text, Path::new("."))
+    }
+
+    #[test]
+    fn builtin_config_is_fine() {
+        assert!(!HoleConfig::builtin().sources.is_empty());
+    }
+
+    #[test]
+    fn dumps_need_no_api_key() {
+        assert!(HoleConfig::builtin().needs_api_key());
+
+        let config = parse("[[source]]\nkind = \"menu\"\npath = \"/TIP\"\ndesc = \"FROG TIPS.\"\n\n  \
+                            [[source.source]]\n  kind = \"tips\"\n  dump = \"tips.jsonl\"\n").unwrap();
+        assert!(!config.needs_api_key());
+    }
+
+    #[test]
+    fn tips_are_refreshed_with_a_pause() {
+        match parse("[[source]]\nkind = \"tips\"\nrefresh = 0\n") {
+            Err(HoleConfigError::ZeroRefresh) => {},
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(parse("[[source]]\nkind = \"tips\"\nrefresh = 1\n").is_ok());
+    }
+
+    #[test]
+    fn unknown_kinds_are_refused() {
+        match parse("[[source]]\nkind = \"toad\"\n") {
+            Err(HoleConfigError::UnknownKind(kind)) => assert_eq!(kind, "toad"),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn duplicate_selectors_are_refused() {
+        let config = "[[source]]\nkind = \"bogus\"\npath = \"/DOCS\"\ndesc = \"ONE\"\n\n\
+                      [[source]]\nkind = \"menu\"\npath = \"/DOCS/\"\ndesc = \"TWO\"\n";
+        match parse(config) {
+            Err(HoleConfigError::DuplicateSelector(path)) => assert_eq!(path, "/DOCS/"),
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let nested = "[[source]]\nkind = \"tips\"\n\n\
+                      [[source]]\nkind = \"menu\"\npath = \"/TIP\"\ndesc = \"MORE\"\n\n  \
+                      [[source.source]]\n  kind = \"tips\"\n";
+        match parse(nested) {
+            Err(HoleConfigError::DuplicateSelector(_)) => {},
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn aliases_point_at_claimed_selectors() {
+        let aliases = HoleConfig::builtin().aliases;
+        assert!(aliases.contains(&("/FROG_MODELS".to_string(), "/DOCS/FROG_MODELS".to_string())));
+
+        let nowhere = "[[source]]\nkind = \"bogus\"\npath = \"/DOCS\"\ndesc = \"DOCS\"\n\n\
+                       [[alias]]\nfrom = \"/README\"\nto = \"/DOCS/README\"\n";
+        match parse(nowhere) {
+            Err(HoleConfigError::UnknownAliasTarget(path)) => assert_eq!(path, "/DOCS/README"),
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let taken = "[[source]]\nkind = \"bogus\"\npath = \"/DOCS\"\ndesc = \"DOCS\"\n\n\
+                     [[alias]]\nfrom = \"/DOCS/\"\nto = \"/DOCS\"\n";
+        match parse(taken) {
+            Err(HoleConfigError::DuplicateSelector(path)) => assert_eq!(path, "/DOCS/"),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn menus_only_list_what_is_under_them() {
+        let elsewhere = "[[source]]\nkind = \"menu\"\npath = \"/DOCS\"\ndesc = \"DOCS\"\n\n  \
+                         [[source.source]]\n  kind = \"bogus\"\n  path = \"/ELSEWHERE\"\n  desc = \"NOWHERE\"\n";
+        match parse(elsewhere) {
+            Err(HoleConfigError::OutsideMenu(path, menu)) => assert_eq!((path.as_str(), menu.as_str()), ("/ELSEWHERE", "/DOCS")),
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let frogs = "[[source]]\nkind = \"menu\"\npath = \"/DOCS/\"\ndesc = \"DOCS\"\n\n  \
+                     [[source.source]]\n  kind = \"genuine_frog\"\n";
+        match parse(frogs) {
+            Err(HoleConfigError::OutsideMenu(path, _)) => assert_eq!(path, "/GENUINEFROG"),
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        let lookalike = "[[source]]\nkind = \"menu\"\npath = \"/DOCS\"\ndesc = \"DOCS\"\n\n  \
+                         [[source.source]]\n  kind = \"bogus\"\n  path = \"/DOCSTRINGS\"\n  desc = \"NOWHERE\"\n";
+        assert!(parse(lookalike).is_err());
+    }
+
+    #[test]
+    fn manuals_come_from_files() {
+        let builtin = parse("[[source]]\nkind = \"manual\"\npath = \"/MANUAL\"\ndesc = \"MANUAL.\"\n").unwrap();
+        let from_disk = parse("[[source]]\nkind = \"manual\"\npath = \"/MANUAL\"\ndesc = \"MANUAL.\"\ndir = \"txt/USER_MANUAL\"\n").unwrap();
+        match (&builtin.sources[0], &from_disk.sources[0]) {
+            (&SourceConfig::Manual {pages: ref builtin, ..}, &SourceConfig::Manual {pages: ref from_disk, ..}) => {
+                assert_eq!(builtin, from_disk);
+            },
+            _ => panic!("expected manuals"),
+        }
+    }
+
+    #[test]
+    fn manual_pages_cannot_be_searches() {
+        let dir = env::temp_dir().join("frog_gopher_manual_search");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        File::create(dir.join("CHAPTER_01")).unwrap().write_all(b"FROG.").unwrap();
+        File::create(dir.join("SEARCH")).unwrap().write_all(b"HOW TO FIND FROG.").unwrap();
+
+        let config = format!("[[source]]\nkind = \"manual\"\npath = \"/MANUAL\"\ndesc = \"MANUAL.\"\ndir = \"{}\"\n", dir.display());
+        match parse(&config) {
+            Err(HoleConfigError::ReservedPage(file)) => assert_eq!(file, dir.join("SEARCH")),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn binaries_must_exist() {
+        let config = parse("[[source]]\nkind = \"binary\"\npath = \"/DOCS/README\"\ndesc = \"README.\"\nfile = \"txt/README\"\n").unwrap();
+        assert!(match config.sources[0] { SourceConfig::Binary {..} => true, _ => false });
+
+        match parse("[[source]]\nkind = \"binary\"\npath = \"/FIRMWARE\"\ndesc = \"FIRMWARE.\"\nfile = \"txt/NO_SUCH_FIRMWARE.bin\"\n") {
+            Err(HoleConfigError::Io(..)) => {},
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_fields_are_named() {
+        match parse("[[source]]\nkind = \"text\"\npath = \"/README\"\nbuiltin = \"README\"\n") {
+            Err(HoleConfigError::MissingField(kind, field)) => {
+                assert_eq!(kind, "text");
+                assert_eq!(field, "desc");
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn claims_what_the_hole_serves() {
+        let config = HoleConfig::builtin();
+        assert!(config.claims(""));
+        assert!(config.claims("/"));
+        assert!(config.claims("/TIP/3"));
+        assert!(config.claims("/GENUINEFROG/"));
+        assert!(config.claims("/FROG_MODELS"));
+        assert!(!config.claims("/SECRET/STATS"));
+        assert!(!config.claims("/TIPS"));
+    }
+}
@@ -0,0 +1,311 @@
+// The same gopherhole, as web pages for browsers that forgot how to gopher.
+// Paths follow gopher URLs, and searches come in from a form as ?q=
+
+use std::io;
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use futures::{future,Async,Future,Poll};
+use tokio::io::{AsyncRead,AsyncWrite};
+use url::Url;
+
+use protocol::{self,Menu,MenuItem,Path,Selected,Selector};
+use the_impl_ya_dummy::{Gopher,hang_up};
+use gopher_url::{self,Route};
+use rate_limit::SLOW_DOWN;
+use access_log::{Outcome,Served};
+
+
+// Nobody needs more headers than this to ask for a frog tip
+const MAX_HEAD_LEN: usize = 8192;
+
+// The only URL schemes menus may link browsers to
+const LINKABLE: [&'static str; 4] = ["http", "https", "gopher", "gemini"];
+
+// Reads the request line and headers, up to the blank line that ends them
+struct ReadHead<S> {
+    stream: Option<S>,
+    head: Vec<u8>,
+}
+
+impl<S: AsyncRead> Future for ReadHead<S> {
+    type Item = (S, String);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, io::Error> {
+        let mut buf = [0; 1024];
+        loop {
+            let len = try_ready!(self.stream.as_mut().expect("polled ReadHead after it finished").poll_read(&mut buf));
+            if len == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the browser hung up"));
+            }
+            self.head.extend_from_slice(&buf[..len]);
+
+            let end = self.head.windows(4).position(|w| w == b"\r\n\r\n")
+                          .or(self.head.windows(2).position(|w| w == b"\n\n"));
+            if let Some(end) = end {
+                let head = String::from_utf8_lossy(&self.head[..end]).into_owned();
+                return Ok(Async::Ready((self.stream.take().unwrap(), head)));
+            }
+
+            if self.head.len() > MAX_HEAD_LEN {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "the headers are too big"));
+            }
+        }
+    }
+}
+
+#[derive(Debug,Eq,PartialEq)]
+enum Request {
+    Get(Route),
+    // The same, minus the body
+    Head(Route),
+    NotAllowed,
+    Bad,
+}
+
+fn parse(head: &str) -> Request {
+    let mut words = head.lines().next().unwrap_or("").split_whitespace();
+    let (method, target) = match (words.next(), words.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return Request::Bad,
+    };
+
+    // Targets are usually just a path, but may be a whole URL
+    let url = match Url::parse("http://frog.tips/").and_then(|base| base.join(target)) {
+        Ok(url) => url,
+        Err(_) => return Request::Bad,
+    };
+    let terms = url.query_pairs()
+                   .find(|&(ref key, _)| key == "q")
+                   .map(|(_, terms)| terms.into_owned());
+    let route = gopher_url::route(url.path(), terms);
+
+    match method {
+        "GET" => Request::Get(route),
+        "HEAD" => Request::Head(route),
+        _ => Request::NotAllowed,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+            escape(title), body)
+}
+
+fn search_form(path: &Path, desc: &str) -> String {
+    format!("<form action=\"{}\"><label>{} <input name=\"q\"></label> <input type=\"submit\" value=\"SEARCH\"></form>",
+            escape(&gopher_url::link('7', path)), escape(desc))
+}
+
+fn write_menu(body: &mut String, menu: &Menu) {
+    body.push_str("<ul>\n");
+    for item in menu.items() {
+        let line = match item {
+            MenuItem::Text {ref path, ref desc} =>
+                format!("<a href=\"{}\">{}</a>", escape(&gopher_url::link('0', path)), escape(desc)),
+            MenuItem::Directory {ref path, ref desc} =>
+                format!("<a href=\"{}\">{}</a>", escape(&gopher_url::link('1', path)), escape(desc)),
+            MenuItem::JohnGoerzenUrl {ref url, ref desc} if LINKABLE.contains(&url.scheme()) =>
+                format!("<a href=\"{}\">{}</a>", escape(url.as_str()), escape(desc)),
+            // Anything else could run in the page, so it is only named
+            MenuItem::JohnGoerzenUrl {ref desc, ..} => escape(desc),
+            MenuItem::Search {ref path, ref desc} => search_form(path, desc),
+            MenuItem::Info {ref desc} => format!("<pre>{}</pre>", escape(desc)),
+            MenuItem::Binary {ref path, ref desc} |
+            MenuItem::Gif {ref path, ref desc} |
+            MenuItem::Image {ref path, ref desc} |
+            MenuItem::Archive {ref path, ref desc} |
+            MenuItem::Sound {ref path, ref desc} =>
+                format!("<a href=\"{}\">{}</a>", escape(&gopher_url::link(item.item_type(), path)), escape(desc)),
+            MenuItem::Elsewhere {item_type, ref path, ref desc, ref host, port} =>
+                format!("<a href=\"{}\">{}</a>", escape(&gopher_url::elsewhere(host, port, item_type, path)), escape(desc)),
+        };
+        body.push_str(&format!("<li>{}</li>\n", line));
+    }
+    body.push_str("</ul>\n");
+}
+
+// The status line and the page, for anything but a binary
+fn render(selected: &Selected) -> (&'static str, String) {
+    let mut body = String::new();
+    let status = match selected {
+        &Selected::Error(ref why) => {
+            body.push_str(&format!("<p>{}</p>\n", escape(why)));
+            "404 Not Found"
+        },
+        &Selected::SlowDown => {
+            body.push_str(&format!("<p>{}</p>\n", escape(SLOW_DOWN)));
+            "429 Too Many Requests"
+        },
+        &Selected::Failed(ref why) => {
+            body.push_str(&format!("<p>{}</p>\n", escape(why)));
+            "500 Internal Server Error"
+        },
+        &Selected::Text(ref text) => {
+            body.push_str(&format!("<pre>{}</pre>\n", escape(text)));
+            "200 OK"
+        },
+        &Selected::ForeverMenu(ref menu) => {
+            write_menu(&mut body, *menu);
+            "200 OK"
+        },
+        &Selected::TempMenu(ref menu) => {
+            write_menu(&mut body, &**menu);
+            "200 OK"
+        },
+        &Selected::Binary(_) => unreachable!("binaries are sent as they are"),
+    };
+    (status, body)
+}
+
+fn reply_with(status: &str, content_type: &str, body: &[u8], with_body: bool) -> Vec<u8> {
+    let mut resp = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                           status, content_type, body.len()).into_bytes();
+    if with_body {
+        resp.extend_from_slice(body);
+    }
+    resp
+}
+
+fn reply(status: &str, title: &str, body: &str, with_body: bool) -> Vec<u8> {
+    reply_with(status, "text/html; charset=utf-8", page(title, body).as_bytes(), with_body)
+}
+
+// What to find for the browser, with the item type and whether to send a body, or what to tell it instead
+fn route(head: &str) -> Result<(Selector, char, bool), (Vec<u8>, Served)> {
+    let (route, with_body) = match parse(head) {
+        Request::Get(route) => (route, true),
+        Request::Head(route) => (route, false),
+        Request::NotAllowed => return Err((reply("405 Method Not Allowed", "FROG.TIPS", "<p>FROG ONLY GETS THINGS.</p>\n", true),
+                                           Served::nothing(Outcome::Bad))),
+        Request::Bad => return Err((reply("400 Bad Request", "FROG.TIPS", "<p>FROG DOES NOT UNDERSTAND.</p>\n", true),
+                                    Served::nothing(Outcome::Bad))),
+    };
+
+    // Binaries are sent as whatever type the link promised
+    match route {
+        Route::Menu(selector) | Route::Text(selector) => Ok((selector, '9', with_body)),
+        Route::Binary(item_type, selector) => Ok((selector, item_type, with_body)),
+        Route::Search(ref search) if search.extra().is_none() => {
+            let form = format!("{}\n", search_form(search, "WHAT ARE YOU LOOKING FOR?"));
+            Err((reply("200 OK", search.val(), &form, with_body), Served::new(search.val().clone(), '7', Outcome::Served)))
+        },
+        Route::Search(search) => Ok((Selector::Path(search), '9', with_body)),
+        Route::NotFound(path) => {
+            let why = format!("<p>{} NOT FOUND</p>\n", escape(&path));
+            Err((reply("404 Not Found", &path, &why, with_body), Served::new(path, '3', Outcome::NotFound)))
+        },
+    }
+}
+
+fn answer(gopher: Arc<Gopher>, peer: IpAddr, head: &str) -> Box<Future<Item=(Vec<u8>, Served), Error=io::Error> + Send> {
+    let (selector, item_type, with_body) = match route(head) {
+        Ok(route) => route,
+        Err(answered) => return Box::new(future::ok(answered)),
+    };
+
+    Gopher::find_then(gopher, Some(peer), selector, move |_, selector, found| {
+        let title = match selector {
+            &Selector::Path(ref path) => path.val().clone(),
+            &Selector::Empty => "FROG.TIPS".to_string(),
+        };
+
+        let served = Served::found(selector, &found, item_type);
+        if let Selected::Binary(ref bytes) = found {
+            return Ok((reply_with("200 OK", protocol::binary_mime(item_type, &title), bytes, with_body), served));
+        }
+
+        let (status, body) = render(&found);
+        Ok((reply(status, &title, &body, with_body), served))
+    })
+}
+
+// Reads what the browser wants and answers it with a page of what a gopher would have been sent
+pub fn respond<S>(gopher: Arc<Gopher>, stream: S, peer: IpAddr) -> Box<Future<Item=Served, Error=io::Error> + Send>
+    where S: AsyncRead + AsyncWrite + Send + 'static {
+    let read = ReadHead {
+        stream: Some(stream),
+        head: Vec::new(),
+    };
+
+    let answered = read.and_then(move |(stream, head)| {
+        answer(gopher, peer, &head).map(move |(resp, served)| (stream, resp, served))
+    });
+
+    Box::new(answered.and_then(|(stream, resp, served)| hang_up(stream, resp, served)))
+}
+
+// Sends the browser away without listening to what it wanted
+pub fn turn_away<W: Write>(mut stream: W, why: &str) -> io::Result<()> {
+    stream.write_all(&reply("503 Service Unavailable", "FROG.TIPS", &format!("<p>{}</p>\n", escape(why)), true))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use protocol::{Menu,MenuItem,Path,Selected,Selector};
+    use gopher_url::Route;
+    use super::{parse,escape,render,write_menu,Request};
+
+
+    struct Links(Vec<MenuItem>);
+
+    impl Menu for Links {
+        fn items(&self) -> Vec<MenuItem> {
+            self.0.clone()
+        }
+    }
+
+
+    #[test]
+    fn request_lines_become_routes() {
+        assert_eq!(parse("GET / HTTP/1.1\r\nHost: frog.tips"), Request::Get(Route::Menu(Selector::Empty)));
+        assert_eq!(parse("HEAD /1/DOCS HTTP/1.0"), Request::Head(Route::Menu(Selector::Path(Path::from("/DOCS")))));
+        assert_eq!(parse("GET http://frog.tips/0/DOCS/FROG_MODELS HTTP/1.1"),
+                   Request::Get(Route::Text(Selector::Path(Path::from("/DOCS/FROG_MODELS")))));
+        assert_eq!(parse("POST / HTTP/1.1"), Request::NotAllowed);
+        assert_eq!(parse("FROG"), Request::Bad);
+    }
+
+    #[test]
+    fn searches_come_from_forms() {
+        assert_eq!(parse("GET /7/TIP/SEARCH?q=FROG+TOY HTTP/1.1"),
+                   Request::Get(Route::Search(Path::new("/TIP/SEARCH", Some("FROG TOY")))));
+        assert_eq!(parse("GET /7/TIP/SEARCH HTTP/1.1"), Request::Get(Route::Search(Path::from("/TIP/SEARCH"))));
+    }
+
+    #[test]
+    fn only_web_and_hole_urls_are_links() {
+        let url = |url: &str| MenuItem::JohnGoerzenUrl { url: Url::parse(url).unwrap(), desc: "FROG".to_string() };
+        let mut body = String::new();
+        write_menu(&mut body, &Links(vec![url("https://frog.tips"), url("gemini://frog.tips"),
+                                          url("javascript:alert(1)"), url("data:text/html,FROG")]));
+
+        assert!(body.contains("<a href=\"https://frog.tips/\">FROG</a>"));
+        assert!(body.contains("<a href=\"gemini://frog.tips/\">FROG</a>"));
+        assert!(!body.contains("javascript:"));
+        assert!(!body.contains("data:"));
+    }
+
+    #[test]
+    fn failures_are_server_errors() {
+        assert_eq!(render(&Selected::Error(Box::new("/FROG NOT FOUND".to_string()))).0, "404 Not Found");
+        assert_eq!(render(&Selected::Failed(Box::new("FROG COULD NOT READ /FROG".to_string()))).0, "500 Internal Server Error");
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        assert_eq!(escape("<FROG & \"TOAD\">"), "&lt;FROG &amp; &quot;TOAD&quot;&gt;");
+    }
+}
@@ -0,0 +1,174 @@
+// Asks a web server for one thing without tying up a thread while it answers. Only as much
+// HTTP as frog.tips needs: one GET, one answer, then the connection is closed.
+
+use std::io;
+use std::net::ToSocketAddrs;
+
+use futures::{future,Async,Future,Poll};
+use native_tls;
+use tokio::io::{AsyncRead,AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_tls::TlsConnector;
+use url::Url;
+
+use the_impl_ya_dummy::in_a_burrow;
+
+
+// Nothing frog.tips says about one tip comes anywhere near this
+const MAX_RESPONSE_LEN: usize = 1024 * 1024;
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+fn invalid<E: Into<Box<::std::error::Error + Send + Sync>>>(why: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, why)
+}
+
+// The status, where the body starts and how long it is, once the whole head is in
+fn parse_head(resp: &[u8]) -> Option<io::Result<(u16, usize, Option<usize>)>> {
+    let end = match resp.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(end) => end,
+        None => return None,
+    };
+
+    let head = String::from_utf8_lossy(&resp[..end]);
+    let mut lines = head.lines();
+    let status = match lines.next().and_then(|line| line.split_whitespace().nth(1)).map(str::parse) {
+        Some(Ok(status)) => status,
+        _ => return Some(Err(invalid("no status line"))),
+    };
+
+    let mut len = None;
+    for line in lines {
+        let mut header = line.splitn(2, ':');
+        match (header.next(), header.next()) {
+            (Some(name), Some(value)) if name.trim().eq_ignore_ascii_case("content-length") => match value.trim().parse() {
+                Ok(value) => len = Some(value),
+                Err(_) => return Some(Err(invalid("bad Content-Length"))),
+            },
+            _ => {},
+        }
+    }
+
+    Some(Ok((status, end + 4, len)))
+}
+
+// Reads until the server hangs up or has sent as much as it said it would
+struct ReadResponse<S> {
+    stream: S,
+    resp: Vec<u8>,
+}
+
+impl<S: AsyncRead> ReadResponse<S> {
+    fn done(&self, hung_up: bool) -> Option<io::Result<Response>> {
+        let (status, start, len) = match parse_head(&self.resp) {
+            Some(Ok(head)) => head,
+            Some(Err(why)) => return Some(Err(why)),
+            None if hung_up => return Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the server hung up early"))),
+            None => return None,
+        };
+
+        let body = &self.resp[start..];
+        match len {
+            Some(len) if body.len() >= len => Some(Ok(Response { status: status, body: body[..len].to_vec() })),
+            Some(_) if hung_up => Some(Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the body was cut short"))),
+            None if hung_up => Some(Ok(Response { status: status, body: body.to_vec() })),
+            _ => None,
+        }
+    }
+}
+
+impl<S: AsyncRead> Future for ReadResponse<S> {
+    type Item = Response;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Response, io::Error> {
+        let mut buf = [0; 4096];
+        loop {
+            let len = try_ready!(self.stream.poll_read(&mut buf));
+            self.resp.extend_from_slice(&buf[..len]);
+            if let Some(resp) = self.done(len == 0) {
+                return resp.map(Async::Ready);
+            }
+
+            if self.resp.len() > MAX_RESPONSE_LEN {
+                return Err(invalid("the response is too big"));
+            }
+        }
+    }
+}
+
+fn exchange<S>(stream: S, request: String) -> Box<Future<Item=Response, Error=io::Error> + Send>
+    where S: AsyncRead + AsyncWrite + Send + 'static {
+    Box::new(::tokio::io::write_all(stream, request.into_bytes()).and_then(|(stream, _)| ReadResponse {
+        stream: stream,
+        resp: Vec::new(),
+    }))
+}
+
+// Setting one up means loading the system's certificates, so build it once and hand it to every get
+pub fn tls() -> io::Result<TlsConnector> {
+    native_tls::TlsConnector::new()
+        .map(TlsConnector::from)
+        .map_err(|why| io::Error::new(io::ErrorKind::Other, why))
+}
+
+// GETs the URL with the given headers, over TLS for https
+pub fn get(url: &Url, headers: &[(&str, &str)], tls: &TlsConnector) -> Box<Future<Item=Response, Error=io::Error> + Send> {
+    let secure = match url.scheme() {
+        "http" => false,
+        "https" => true,
+        scheme => return Box::new(future::err(io::Error::new(io::ErrorKind::InvalidInput,
+                                                             format!("cannot GET {} URLs", scheme)))),
+    };
+    let (host, port) = match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) => (host.to_string(), port),
+        _ => return Box::new(future::err(io::Error::new(io::ErrorKind::InvalidInput, "the URL has no host"))),
+    };
+
+    let mut request = format!("GET {}", url.path());
+    if let Some(query) = url.query() {
+        request.push_str(&format!("?{}", query));
+    }
+    request.push_str(&format!(" HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n", host));
+    for &(name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+
+    // Looking the host up is the one part that has to block
+    let lookup = host.clone();
+    let addr = in_a_burrow(move || match try!((lookup.as_str(), port).to_socket_addrs()).next() {
+        Some(addr) => Ok(addr),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, format!("{} has no address", lookup))),
+    });
+    let connected = addr.and_then(|addr| TcpStream::connect(&addr));
+
+    if !secure {
+        return Box::new(connected.and_then(move |stream| exchange(stream, request)));
+    }
+
+    let connector = tls.clone();
+    Box::new(connected.and_then(move |stream| {
+        connector.connect(&host, stream).map_err(|why| io::Error::new(io::ErrorKind::Other, why))
+    }).and_then(move |stream| exchange(stream, request)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::parse_head;
+
+
+    #[test]
+    fn heads_say_how_long_the_body_is() {
+        let resp = b"HTTP/1.1 200 OK\r\ncontent-length: 4\r\n\r\nFROG";
+        assert_eq!(parse_head(resp).unwrap().unwrap(), (200, 38, Some(4)));
+        assert_eq!(parse_head(b"HTTP/1.0 404 Not Found\r\n\r\n").unwrap().unwrap(), (404, 26, None));
+        assert!(parse_head(b"HTTP/1.1 200 OK\r\nContent-Le").is_none());
+        assert!(parse_head(b"FROG\r\n\r\n").unwrap().is_err());
+    }
+}
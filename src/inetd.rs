@@ -0,0 +1,149 @@
+// Answers one gopher on stdin and stdout, then goes away. For inetd, xinetd or systemd with
+// Accept=yes, which pick the gopher up themselves and hand frog the socket as both.
+
+use std::io::{self,Read,Write};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use futures::{Async,Poll};
+use tokio::io::{AsyncRead,AsyncWrite};
+use tokio::runtime::Runtime;
+
+use the_impl_ya_dummy::Gopher;
+use rate_limit::{Admission,SLOW_DOWN};
+use access_log::{Outcome,Served};
+
+
+// A reader and a writer that make one stream between them
+pub struct Duplex<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R: Read, W> Read for Duplex<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl<R, W: Write> Write for Duplex<R, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<R: Read, W> AsyncRead for Duplex<R, W> {}
+
+impl<R, W: Write> AsyncWrite for Duplex<R, W> {
+    // Whoever handed over the writer closes it
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        try!(self.writer.flush());
+        Ok(Async::Ready(()))
+    }
+}
+
+// Reads what the gopher wants from the reader and answers it on the writer. A gopher nobody knows
+// the address of is on no list, so it is neither refused nor let off anything.
+pub fn respond<R, W>(gopher: Arc<Gopher>, reader: R, mut writer: W, peer: Option<IpAddr>) -> io::Result<Served>
+    where R: Read + Send + 'static, W: Write + Send + 'static {
+    match peer.map_or(Admission::Welcome, |peer| gopher.admit(&peer)) {
+        Admission::Welcome => {},
+        Admission::SlowDown => {
+            try!(gopher.turn_away(&mut writer, SLOW_DOWN));
+            try!(writer.flush());
+            return Ok(Served::nothing(Outcome::SlowDown));
+        },
+        Admission::Refused => return Ok(Served::nothing(Outcome::Refused)),
+    }
+
+    // Sources expect somewhere to block
+    let mut runtime = try!(Runtime::new());
+    runtime.block_on(Gopher::respond(gopher, Duplex { reader: reader, writer: writer }, peer))
+}
+
+// Whoever is on the other end of stdin, if it is a TCP socket at all. Pipes and UNIX sockets
+// could be anyone.
+#[cfg(unix)]
+pub fn peer() -> Option<IpAddr> {
+    use std::net::TcpStream;
+    use std::os::unix::io::{FromRawFd,IntoRawFd};
+
+    // Only borrowed, so it is let go again without being closed
+    let stdin = unsafe { TcpStream::from_raw_fd(0) };
+    let addr = stdin.peer_addr();
+    let _ = stdin.into_raw_fd();
+    addr.map(|addr| addr.ip()).ok()
+}
+
+#[cfg(not(unix))]
+pub fn peer() -> Option<IpAddr> {
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use mock_frog_tips;
+    use shared_buffer::Shared;
+    use protocol::ExternalAddr;
+    use rate_limit::{Limit,RateLimiter};
+    use access_log::Outcome;
+    use stats::StatsConfig;
+    use the_impl_ya_dummy::Gopher;
+    use super::respond;
+
+
+    fn gopher() -> Gopher {
+        Gopher::new(ExternalAddr::new("127.0.0.1", 70), "testing".to_string(), &mock_frog_tips::hole_without_tips())
+    }
+
+    fn ask(gopher: Gopher, peer: Option<&str>, line: &str) -> (String, Outcome) {
+        let out = Shared::new();
+        let reader = Cursor::new(line.as_bytes().to_vec());
+        let served = respond(Arc::new(gopher), reader, out.clone(), peer.map(|peer| peer.parse().unwrap())).unwrap();
+        let resp = out.contents();
+        (resp, served.outcome)
+    }
+
+    #[test]
+    fn gophers_can_be_answered_in_memory() {
+        let (menu, outcome) = ask(gopher(), Some("192.0.2.1"), "/DOCS\r\n");
+        assert_eq!(outcome, Outcome::Served);
+        assert!(menu.contains("0NON-CANON FROG MODEL LISTING.\t/DOCS/FROG_MODELS\t127.0.0.1\t70\t+\r\n"));
+        assert!(menu.ends_with(".\r\n"));
+
+        let (missing, outcome) = ask(gopher(), Some("192.0.2.1"), "/NOTHING\r\n");
+        assert_eq!(missing, "3/NOTHING NOT FOUND\r\n.\r\n");
+        assert_eq!(outcome, Outcome::NotFound);
+    }
+
+    #[test]
+    fn denied_gophers_get_nothing() {
+        let mut gopher = gopher();
+        let no_limit = Limit::unlimited();
+        gopher.set_rate_limiter(RateLimiter::new(no_limit, no_limit, vec![], vec!["192.0.2.0/24".parse().unwrap()]));
+
+        assert_eq!(ask(gopher, Some("192.0.2.1"), "/DOCS\r\n"), (String::new(), Outcome::Refused));
+    }
+
+    #[test]
+    fn unknown_gophers_are_on_no_list() {
+        let mut gopher = gopher();
+        let no_limit = Limit::unlimited();
+        let everyone = vec!["0.0.0.0/0".parse().unwrap(), "::/0".parse().unwrap()];
+        gopher.set_rate_limiter(RateLimiter::new(no_limit, no_limit, vec![], everyone.clone()));
+        gopher.set_stats(StatsConfig {
+            selector: "/STATS".to_string(),
+            allow: everyone,
+        });
+
+        assert_eq!(ask(gopher, None, "/STATS\r\n"), ("3/STATS NOT FOUND\r\n.\r\n".to_string(), Outcome::NotFound));
+    }
+}
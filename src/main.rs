@@ -1,3 +1,5 @@
+#![cfg_attr(feature = "bench", feature(test))]
+
 #[macro_use]
 extern crate log;
 
@@ -10,33 +12,124 @@ extern crate hyper;
 extern crate getopts;
 extern crate time;
 extern crate reqwest;
+extern crate toml;
+extern crate ctrlc;
+#[macro_use]
+extern crate futures;
+extern crate tokio;
+extern crate tokio_threadpool;
+extern crate native_tls;
+extern crate tokio_tls;
+extern crate url;
+extern crate rand;
+#[cfg(test)]
+extern crate openssl;
 
 mod frog_log;
 mod protocol;
+mod hole_config;
 mod the_impl_ya_dummy;
+mod gopher_pool;
+mod gopher_url;
+mod gemini;
+mod http_gateway;
+mod http_get;
+mod finger;
+mod cidr;
+mod rate_limit;
+mod access_log;
+mod metrics;
+mod stats;
+mod inetd;
+#[cfg(test)]
+mod mock_frog_tips;
+#[cfg(test)]
+mod shared_buffer;
+#[cfg(all(test, feature = "bench"))]
+mod benches;
 
 mod cli {
     use getopts::Options;
     use std::env;
-    use std::net::{SocketAddr,AddrParseError};
+    use std::net::{IpAddr,Ipv4Addr,SocketAddr,AddrParseError};
+    use std::num::ParseIntError;
+    use std::time::Duration;
     use std::error;
+    use std::path::PathBuf;
 
     use super::protocol::{ExternalAddr,ParseExternalAddrError};
+    use super::hole_config::{HoleConfig,HoleConfigError,DEFAULT_TIP_API_URL};
+    use super::cidr::{Cidr,ParseCidrError};
+    use super::rate_limit::Limit;
+    use super::access_log::{self,ParseFormatError};
+    use super::frog_log::{self,LogConfig,ParseLogConfigError};
+    use super::stats::StatsConfig;
 
 
     const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+    pub const DEFAULT_WORKERS: usize = 16;
+    pub const DEFAULT_MAX_WAITING: usize = 1024;
+    pub const DEFAULT_MAX_CONNECTIONS: usize = 10000;
+    const DEFAULT_GRACE_SECS: u64 = 10;
 
     #[derive(Clone, Debug)]
     pub struct Config {
         pub int_addr: SocketAddr,
         pub ext_addr: ExternalAddr,
         pub frog_tips_api_key: String,
+        pub hole: HoleConfig,
+        pub workers: usize,
+        pub max_waiting: usize,
+        pub max_connections: usize,
+        pub grace: Duration,
+        pub tls_addr: Option<SocketAddr>,
+        pub gemini_addr: Option<SocketAddr>,
+        pub http_addr: Option<SocketAddr>,
+        pub finger_addr: Option<SocketAddr>,
+        pub metrics_addr: Option<SocketAddr>,
+        pub tls: Option<TlsConfig>,
+        pub rate: Limit,
+        pub tip_rate: Limit,
+        pub allow: Vec<Cidr>,
+        pub deny: Vec<Cidr>,
+        pub access_log: Option<AccessLogConfig>,
+        pub log: LogConfig,
+        pub stats: Option<StatsConfig>,
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct AccessLogConfig {
+        pub file: PathBuf,
+        pub format: access_log::Format,
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct TlsConfig {
+        // PEM, with the key in PKCS#8
+        pub cert: PathBuf,
+        pub key: PathBuf,
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct ExportConfig {
+        pub api_url: String,
+        pub frog_tips_api_key: String,
+        pub file: PathBuf,
+    }
+
+    #[derive(Clone, Debug)]
+    pub enum Command {
+        Serve(Config),
+        // One gopher on stdin and stdout
+        Inetd(Config),
+        ExportTips(ExportConfig),
     }
 
     enum Error<'a> {
         Usage(&'a str, Options),
         BadOpt(Box<error::Error>),
         MissingOpt(String),
+        BadConfig(HoleConfigError),
         Version,
     }
 
@@ -52,15 +145,69 @@ mod cli {
         }
     }
 
+    impl<'a> From<ParseIntError> for Error<'a> {
+        fn from(err: ParseIntError) -> Error<'a> {
+            Error::BadOpt(Box::new(err))
+        }
+    }
+
+    impl<'a> From<ParseCidrError> for Error<'a> {
+        fn from(err: ParseCidrError) -> Error<'a> {
+            Error::BadOpt(Box::new(err))
+        }
+    }
+
+    impl<'a> From<ParseFormatError> for Error<'a> {
+        fn from(err: ParseFormatError) -> Error<'a> {
+            Error::BadOpt(Box::new(err))
+        }
+    }
+
+    impl<'a> From<ParseLogConfigError> for Error<'a> {
+        fn from(err: ParseLogConfigError) -> Error<'a> {
+            Error::BadOpt(Box::new(err))
+        }
+    }
+
+    impl<'a> From<HoleConfigError> for Error<'a> {
+        fn from(err: HoleConfigError) -> Error<'a> {
+            Error::BadConfig(err)
+        }
+    }
+
     fn print_usage(program: &str, opts: Options) {
-        let brief = format!("usage: {} ADDR [OPTIONS]", program);
+        let brief = format!("usage: {0} ADDR [OPTIONS]\n       {0} inetd [OPTIONS]\n       {0} export-tips FILE [OPTIONS]", program);
         print!("{}", opts.usage(&brief));
     }
 
-    fn parse<'a>(program: &'a str, args: &Vec<String>) -> Result<Config, Error<'a>> {
+    fn parse<'a>(program: &'a str, args: &Vec<String>) -> Result<Command, Error<'a>> {
         let mut opts = Options::new();
         opts.optopt("x", "ext-addr", "EXTERNAL ADDRESS.", "EXT_ADDR");
         opts.optopt("k", "api-key", "YOUR FROG.TIPS API KEY.", "API_KEY");
+        opts.optopt("c", "config", "LAY OUT THE GOPHERHOLE ACCORDING TO THIS TOML FILE.", "CONFIG");
+        opts.optopt("w", "workers", &format!("HOW MANY GOPHERS TO LOOK THINGS UP FOR AT ONCE. DEFAULTS TO {}.", DEFAULT_WORKERS), "WORKERS");
+        opts.optopt("q", "max-waiting", &format!("HOW MANY GOPHERS MAY WAIT FOR A WORKER BEFORE FROG IS BUSY. DEFAULTS TO {}.", DEFAULT_MAX_WAITING), "MAX_WAITING");
+        opts.optopt("", "max-connections", &format!("HOW MANY GOPHERS MAY HANG AROUND AT ONCE. KEEP IT UNDER ulimit -n. DEFAULTS TO {}.", DEFAULT_MAX_CONNECTIONS), "MAX_CONNECTIONS");
+        opts.optopt("g", "grace", &format!("SECONDS TO LET GOPHERS FINISH WHEN ASKED TO STOP. DEFAULTS TO {}.", DEFAULT_GRACE_SECS), "GRACE");
+        opts.optopt("u", "api-url", "EXPORT TIPS FROM THIS FROG.TIPS API.", "API_URL");
+        opts.optopt("s", "tls-addr", "ALSO PLAY WITH GOPHERS OVER TLS ON THIS ADDRESS.", "TLS_ADDR");
+        opts.optopt("", "gemini-addr", "ALSO SERVE THE HOLE OVER GEMINI ON THIS ADDRESS, USUALLY PORT 1965.", "GEMINI_ADDR");
+        opts.optopt("", "http-addr", "ALSO SHOW THE HOLE TO WEB BROWSERS ON THIS ADDRESS.", "HTTP_ADDR");
+        opts.optopt("", "finger-addr", "ALSO HAND OUT TIPS TO FINGER ON THIS ADDRESS, USUALLY PORT 79.", "FINGER_ADDR");
+        opts.optopt("", "metrics-addr", "ALSO LET PROMETHEUS SCRAPE METRICS ON THIS ADDRESS. KEEP IT LOCAL.", "METRICS_ADDR");
+        opts.optopt("", "stats-selector", "SHOW HOW FROG IS DOING AT THIS SELECTOR, TO --stats-allow ONLY.", "SELECTOR");
+        opts.optmulti("", "stats-allow", "WHO MAY SEE THE STATS. MAY BE GIVEN MORE THAN ONCE. DEFAULTS TO LOCALHOST.", "CIDR");
+        opts.optopt("", "tls-cert", "PEM CERTIFICATE FOR TLS AND GEMINI.", "TLS_CERT");
+        opts.optopt("", "tls-key", "PEM PKCS#8 KEY FOR TLS AND GEMINI.", "TLS_KEY");
+        opts.optopt("", "rate", "HOW MANY TIMES A MINUTE ONE ADDRESS MAY CONNECT. NO LIMIT UNLESS TOLD.", "PER_MINUTE");
+        opts.optopt("", "tip-rate", "HOW MANY TIMES A MINUTE ONE ADDRESS MAY ASK FOR TIPS. NO LIMIT UNLESS TOLD.", "PER_MINUTE");
+        opts.optmulti("", "allow", "NEVER LIMIT ADDRESSES IN THIS BLOCK. MAY BE GIVEN MORE THAN ONCE.", "CIDR");
+        opts.optmulti("", "deny", "NEVER ANSWER ADDRESSES IN THIS BLOCK. MAY BE GIVEN MORE THAN ONCE.", "CIDR");
+        opts.optopt("", "access-log", "WRITE A LINE FOR EVERY REQUEST TO THIS FILE.", "ACCESS_LOG");
+        opts.optopt("", "access-log-format", "common OR json. DEFAULTS TO common.", "FORMAT");
+        opts.optopt("", "log-level", "HOW MUCH TO LOG, WITH MORE OR LESS FOR SOME MODULES: warn,tip_source=debug. DEFAULTS TO info.", "LEVELS");
+        opts.optopt("", "log-output", "stdout, stderr, syslog OR A FILE TO LOG TO. DEFAULTS TO stdout, OR syslog FOR inetd.", "OUTPUT");
+        opts.optopt("", "log-format", "text OR json. DEFAULTS TO text.", "FORMAT");
         opts.optflag("h", "help", "SHOW THIS HELP THEN EXIT.");
         opts.optflag("v", "version", "SHOW THE CURRENT VERSION THEN EXIT.");
 
@@ -77,109 +224,538 @@ mod cli {
             return Err(Error::Version);
         }
 
-        let addr: SocketAddr = if !matches.free.is_empty() {
+        if matches.free.get(0).map(|c| c.as_str()) == Some("export-tips") {
+            let file = match matches.free.get(1) {
+                Some(file) => PathBuf::from(file),
+                None => return Err(Error::Usage(program, opts)),
+            };
+            let api_key = try!(matches.opt_str("k").ok_or(Error::MissingOpt("API_KEY".to_string())));
+
+            return Ok(Command::ExportTips(ExportConfig {
+                api_url: matches.opt_str("u").unwrap_or(DEFAULT_TIP_API_URL.to_string()),
+                frog_tips_api_key: api_key,
+                file: file,
+            }));
+        }
+
+        let inetd = matches.free.get(0).map(|c| c.as_str()) == Some("inetd");
+        // A frog that answers one gopher forgets it as soon as it's gone, so it has nobody to limit
+        // or count. inetd does the listening and the waiting, so nothing else gets answered either.
+        if inetd {
+            for opt in &["rate", "tip-rate", "allow", "stats-selector", "stats-allow",
+                         "workers", "max-waiting", "max-connections", "grace",
+                         "tls-addr", "gemini-addr", "http-addr", "finger-addr", "metrics-addr", "tls-cert", "tls-key"] {
+                if matches.opt_present(opt) {
+                    return Err(Error::BadOpt(format!("--{} MEANS NOTHING TO INETD", opt).into()));
+                }
+            }
+        }
+        let addr: SocketAddr = if inetd {
+            // inetd does the listening
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 0)
+        } else if !matches.free.is_empty() {
             try!(matches.free[0].clone().parse())
         } else {
             return Err(Error::Usage(program, opts));
         };
 
-        let api_key = try!(matches.opt_str("k").ok_or(Error::MissingOpt("API_KEY".to_string())));
         let ext_addr = {
             let opt = try!(matches.opt_str("x").ok_or(Error::MissingOpt("EXT_ADDR".to_string())));
             try!(opt.parse())
         };
+        let workers = match matches.opt_str("w") {
+            Some(opt) => try!(opt.parse()),
+            None => DEFAULT_WORKERS,
+        };
+        // Nobody would ever be answered
+        if workers == 0 {
+            return Err(Error::BadOpt("WORKERS MUST BE AT LEAST 1".into()));
+        }
+        let max_waiting = match matches.opt_str("q") {
+            Some(opt) => try!(opt.parse()),
+            None => DEFAULT_MAX_WAITING,
+        };
+        let max_connections = match matches.opt_str("max-connections") {
+            Some(opt) => try!(opt.parse()),
+            None => DEFAULT_MAX_CONNECTIONS,
+        };
+        let grace = match matches.opt_str("g") {
+            Some(opt) => Duration::from_secs(try!(opt.parse())),
+            None => Duration::from_secs(DEFAULT_GRACE_SECS),
+        };
+        let hole = match matches.opt_str("c") {
+            Some(file) => try!(HoleConfig::load(file)),
+            None => HoleConfig::builtin(),
+        };
+        let tls_addr = match matches.opt_str("s") {
+            Some(opt) => Some(try!(opt.parse())),
+            None => None,
+        };
+        let gemini_addr = match matches.opt_str("gemini-addr") {
+            Some(opt) => Some(try!(opt.parse())),
+            None => None,
+        };
+        let http_addr = match matches.opt_str("http-addr") {
+            Some(opt) => Some(try!(opt.parse())),
+            None => None,
+        };
+        let finger_addr = match matches.opt_str("finger-addr") {
+            Some(opt) => Some(try!(opt.parse())),
+            None => None,
+        };
+        let metrics_addr = match matches.opt_str("metrics-addr") {
+            Some(opt) => Some(try!(opt.parse())),
+            None => None,
+        };
+        // Gemini is always encrypted
+        let tls = if tls_addr.is_some() || gemini_addr.is_some() {
+            Some(TlsConfig {
+                cert: PathBuf::from(try!(matches.opt_str("tls-cert").ok_or(Error::MissingOpt("TLS_CERT".to_string())))),
+                key: PathBuf::from(try!(matches.opt_str("tls-key").ok_or(Error::MissingOpt("TLS_KEY".to_string())))),
+            })
+        } else {
+            None
+        };
+        // Everyone behind a proxy shares an address, so limits are up to whoever runs frog
+        let rate = match matches.opt_str("rate") {
+            Some(opt) => Limit { per_minute: try!(opt.parse()) },
+            None => Limit::unlimited(),
+        };
+        let tip_rate = match matches.opt_str("tip-rate") {
+            Some(opt) => Limit { per_minute: try!(opt.parse()) },
+            None => Limit::unlimited(),
+        };
+        let mut allow = Vec::new();
+        for opt in matches.opt_strs("allow") {
+            allow.push(try!(opt.parse()));
+        }
+        let mut deny = Vec::new();
+        for opt in matches.opt_strs("deny") {
+            deny.push(try!(opt.parse()));
+        }
+        let access_log = match matches.opt_str("access-log") {
+            Some(file) => Some(AccessLogConfig {
+                file: PathBuf::from(file),
+                format: match matches.opt_str("access-log-format") {
+                    Some(opt) => try!(opt.parse()),
+                    None => access_log::Format::Common,
+                },
+            }),
+            None => None,
+        };
+        let stats = match matches.opt_str("stats-selector") {
+            // Anyone else asking for a taken selector would be told the stats aren't there
+            Some(ref selector) if hole.claims(selector) => {
+                return Err(Error::BadOpt(format!("{} IS ALREADY IN THE HOLE", selector).into()));
+            },
+            Some(selector) => {
+                let mut allow = Vec::new();
+                for opt in matches.opt_strs("stats-allow") {
+                    allow.push(try!(opt.parse()));
+                }
+                if allow.is_empty() {
+                    allow = vec![try!("127.0.0.0/8".parse()), try!("::1".parse())];
+                }
+                Some(StatsConfig {
+                    selector: selector,
+                    allow: allow,
+                })
+            },
+            None => None,
+        };
+        let mut log = LogConfig::default();
+        if let Some(opt) = matches.opt_str("log-level") {
+            log.levels = try!(opt.parse());
+        }
+        if let Some(opt) = matches.opt_str("log-output") {
+            log.output = try!(opt.parse());
+        } else if inetd {
+            // Stdout and stderr both go to the gopher
+            log.output = frog_log::Output::Syslog;
+        }
+        if let Some(opt) = matches.opt_str("log-format") {
+            log.format = try!(opt.parse());
+        }
+        // Tips served from a dump can do without
+        let api_key = match matches.opt_str("k") {
+            Some(api_key) => api_key,
+            None if !hole.needs_api_key() => String::new(),
+            None => return Err(Error::MissingOpt("API_KEY".to_string())),
+        };
 
-        Ok(Config {
+        let config = Config {
             int_addr: addr,
             ext_addr: ext_addr,
-            frog_tips_api_key: api_key
-        })
+            frog_tips_api_key: api_key,
+            hole: hole,
+            workers: workers,
+            max_waiting: max_waiting,
+            max_connections: max_connections,
+            grace: grace,
+            tls_addr: tls_addr,
+            gemini_addr: gemini_addr,
+            http_addr: http_addr,
+            finger_addr: finger_addr,
+            metrics_addr: metrics_addr,
+            tls: tls,
+            rate: rate,
+            tip_rate: tip_rate,
+            allow: allow,
+            deny: deny,
+            access_log: access_log,
+            log: log,
+            stats: stats,
+        };
+
+        Ok(if inetd { Command::Inetd(config) } else { Command::Serve(config) })
     }
 
-    pub fn main<F: Fn(Config) -> ()>(success: F) {
+    pub fn main<F: Fn(Command) -> ()>(success: F) {
         let args: Vec<String> = env::args().collect();
         let program = args[0].clone();
 
         match parse(&program, &args) {
-            Ok(config) => success(config),
+            Ok(command) => success(command),
             Err(err) => match err {
                 Error::Usage(program, opts) => print_usage(&program, opts),
                 Error::BadOpt(err) => println!("ERROR: INVALID VALUE: '{}'.", err),
                 Error::MissingOpt(name) => println!("ERROR: {} IS REQUIRED.", name),
+                Error::BadConfig(err) => println!("ERROR: BAD CONFIG: {}", err),
                 Error::Version => println!("{}, version {}", program, VERSION),
             },
         }
     }
 }
 
-use std::net::{TcpListener,TcpStream};
-use std::thread;
+use std::net::{IpAddr,Ipv4Addr,Ipv6Addr,SocketAddr,TcpListener,TcpStream};
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool,Ordering};
+use std::time::Instant;
+use std::ffi::{OsStr,OsString};
+use std::fs::{self,File};
 use std::io;
+use std::io::{Read,Write};
+use std::path::Path;
+use std::process;
+use std::thread;
 
-use the_impl_ya_dummy::Gopher;
+use native_tls::Identity;
+use tokio_tls::TlsAcceptor;
 
+use cli::{Command,Config,ExportConfig,TlsConfig};
+use the_impl_ya_dummy::TipError;
+use rate_limit::{Limit,RateLimiter};
+use access_log::{AccessLog,Outcome,Served};
+use gopher_pool::{Frontend,GopherPool};
 
-fn gopher_it_ha_ha_puns(stream_res: io::Result<TcpStream>, shared_gopher: &Arc<Gopher>) -> Result<thread::JoinHandle<()>, io::Error> {
-    let stream = try!(stream_res);
-    let addr = try!(stream.peer_addr());
 
-    {
-        let just_a_wee_bit = Some(Duration::from_secs(60));
-        let _ = stream.set_read_timeout(just_a_wee_bit);
-        let _ = stream.set_write_timeout(just_a_wee_bit);
+fn gopher_it_ha_ha_puns(stream_res: io::Result<TcpStream>, pool: &GopherPool) -> io::Result<()> {
+    pool.dispatch(try!(stream_res))
+}
+
+fn gopher_it_securely(stream_res: io::Result<TcpStream>, pool: &GopherPool, tls: &TlsAcceptor) -> io::Result<()> {
+    pool.dispatch_tls(try!(stream_res), tls)
+}
+
+fn gemini_it(stream_res: io::Result<TcpStream>, pool: &GopherPool, tls: &TlsAcceptor) -> io::Result<()> {
+    pool.dispatch_to(try!(stream_res), Frontend::Gemini, Some(tls))
+}
+
+fn browse_it(stream_res: io::Result<TcpStream>, pool: &GopherPool) -> io::Result<()> {
+    pool.dispatch_to(try!(stream_res), Frontend::Http, None)
+}
+
+fn finger_it(stream_res: io::Result<TcpStream>, pool: &GopherPool) -> io::Result<()> {
+    pool.dispatch_to(try!(stream_res), Frontend::Finger, None)
+}
+
+// Scrapes are answered one at a time on the acceptor's own thread, so the next one waits in the
+// listen backlog. None of them can take long.
+fn measure_it(stream_res: io::Result<TcpStream>, pool: &GopherPool) -> io::Result<()> {
+    let stream = try!(stream_res);
+    if let Err(why) = metrics::respond(stream, &pool.render_metrics()) {
+        warn!("FROG COULD NOT TELL PROMETHEUS HOW IT IS DOING: {}", why);
     }
+    Ok(())
+}
 
-    let my_thread_name = format!("GOPHER_{}", addr);
-    let my_gopher = shared_gopher.clone();
+fn tls_acceptor(tls: &TlsConfig) -> io::Result<TlsAcceptor> {
+    let mut cert = Vec::new();
+    try!(try!(File::open(&tls.cert)).read_to_end(&mut cert));
+    let mut key = Vec::new();
+    try!(try!(File::open(&tls.key)).read_to_end(&mut key));
 
-    Ok(try!(thread::Builder::new()
-        .name(my_thread_name)
-        .spawn(move || {
-            info!("A GOPHER HAS POPPED OUT OF ITS BURROW.");
+    let acceptor = Identity::from_pkcs8(&cert, &key)
+        .and_then(native_tls::TlsAcceptor::new)
+        .map_err(|why| io::Error::new(io::ErrorKind::InvalidData, why));
 
-            match my_gopher.respond(stream) {
-                Ok(_) => info!("A GOPHER HAS RETREATED INTO ITS BURROW ON GOOD TERMS. GOODBYE GOPHER."),
-                Err(why) => error!("A GOPHER HAS RETREATED INTO ITS BURROW ON BAD TERMS: {}", why),
-            }
-        })))
+    Ok(TlsAcceptor::from(try!(acceptor)))
 }
 
-fn main() {
-    cli::main(|config| {
-        frog_log::init().unwrap();
+// Stops the accept loops once SIGINT or SIGTERM comes along
+fn stop_on_signal(listeners: &[&TcpListener]) -> Result<Arc<AtomicBool>, ctrlc::Error> {
+    let stopping = Arc::new(AtomicBool::new(false));
 
-        info!("FROG IS PREPARING TO PLAY WITH GOPHERS.");
+    // Accepting blocks, so nudge each listener with a connection of our own
+    let nudge_addrs: Vec<SocketAddr> = listeners.iter().map(|listener| {
+        let addr = listener.local_addr().unwrap();
+        match addr.ip() {
+            IpAddr::V4(ip) if ip.is_unspecified() => SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), addr.port()),
+            IpAddr::V6(ip) if ip.is_unspecified() => SocketAddr::new(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1)), addr.port()),
+            _ => addr,
+        }
+    }).collect();
 
-        let listener = TcpListener::bind(config.int_addr).unwrap();
-        let shared_gopher = Arc::new(the_impl_ya_dummy::Gopher::new(config.ext_addr, config.frog_tips_api_key));
+    let my_stopping = stopping.clone();
+    try!(ctrlc::set_handler(move || {
+        if !my_stopping.swap(true, Ordering::SeqCst) {
+            info!("FROG HAS BEEN ASKED TO STOP PLAYING WITH GOPHERS.");
+            for addr in &nudge_addrs {
+                let _ = TcpStream::connect(addr);
+            }
+        }
+    }));
 
-        info!("FROG IS NOW PLAYING WITH GOPHERS");
+    Ok(stopping)
+}
 
+// Runs an accept loop of its own until frog is asked to stop
+fn accept_on_the_side<F>(name: &str, listener: TcpListener, stopping: Arc<AtomicBool>, gopher_it: F) -> io::Result<thread::JoinHandle<()>>
+    where F: Fn(io::Result<TcpStream>) -> io::Result<()> + Send + 'static {
+    thread::Builder::new().name(name.to_string()).spawn(move || {
         for stream_res in listener.incoming() {
-            if let Err(why) = gopher_it_ha_ha_puns(stream_res, &shared_gopher) {
+            if stopping.load(Ordering::SeqCst) {
+                break;
+            }
+
+            if let Err(why) = gopher_it(stream_res) {
                 error!("GOPHER FAILED TO POP OUT OF ITS BURROW: {}", why);
             }
         }
+    })
+}
+
+fn serve(config: Config) {
+    if let Err(why) = frog_log::init(&config.log) {
+        println!("ERROR: FROG COULD NOT START LOGGING: {}", why);
+        return;
+    }
+
+    info!("FROG IS PREPARING TO PLAY WITH GOPHERS.");
+
+    let listener = TcpListener::bind(config.int_addr).unwrap();
+    let tls = match config.tls {
+        Some(ref tls) => match tls_acceptor(tls) {
+            Ok(acceptor) => Some(acceptor),
+            Err(why) => {
+                error!("FROG COULD NOT READ ITS TLS CERTIFICATE OR KEY: {}", why);
+                return;
+            },
+        },
+        None => None,
+    };
+    let tls_listener = config.tls_addr.map(|addr| TcpListener::bind(addr).unwrap());
+    let gemini_listener = config.gemini_addr.map(|addr| TcpListener::bind(addr).unwrap());
+    let http_listener = config.http_addr.map(|addr| TcpListener::bind(addr).unwrap());
+    let finger_listener = config.finger_addr.map(|addr| TcpListener::bind(addr).unwrap());
+    let metrics_listener = config.metrics_addr.map(|addr| TcpListener::bind(addr).unwrap());
+    let mut gopher = the_impl_ya_dummy::Gopher::new(config.ext_addr, config.frog_tips_api_key, &config.hole);
+    gopher.set_rate_limiter(RateLimiter::new(config.rate, config.tip_rate, config.allow, config.deny));
+    if let Some(stats) = config.stats {
+        gopher.set_stats(stats);
+    }
+    let shared_gopher = Arc::new(gopher);
+    let mut pool = GopherPool::new(shared_gopher.clone(), config.workers, config.max_waiting, config.max_connections).unwrap();
+    if let Some(ref access_log) = config.access_log {
+        match AccessLog::open(access_log.format, &access_log.file) {
+            Ok(access_log) => pool.set_access_log(access_log),
+            Err(why) => {
+                error!("FROG COULD NOT OPEN ITS ACCESS LOG {}: {}", access_log.file.display(), why);
+                return;
+            },
+        }
+    }
+    let pool = Arc::new(pool);
+
+    let stopping = {
+        let mut listeners = vec![&listener];
+        listeners.extend(tls_listener.iter());
+        listeners.extend(gemini_listener.iter());
+        listeners.extend(http_listener.iter());
+        listeners.extend(finger_listener.iter());
+        listeners.extend(metrics_listener.iter());
+        stop_on_signal(&listeners).unwrap()
+    };
+
+    let mut sides = Vec::new();
+    if let (Some(side_listener), Some(acceptor)) = (tls_listener, tls.clone()) {
+        let my_pool = pool.clone();
+        info!("FROG IS ALSO PLAYING WITH GOPHERS OVER TLS ON {}.", side_listener.local_addr().unwrap());
+        sides.push(accept_on_the_side("TLS_ACCEPTOR", side_listener, stopping.clone(),
+                                      move |stream_res| gopher_it_securely(stream_res, &my_pool, &acceptor)).unwrap());
+    }
+    if let (Some(side_listener), Some(acceptor)) = (gemini_listener, tls.clone()) {
+        let my_pool = pool.clone();
+        info!("FROG IS ALSO SERVING GEMINI ON {}.", side_listener.local_addr().unwrap());
+        sides.push(accept_on_the_side("GEMINI_ACCEPTOR", side_listener, stopping.clone(),
+                                      move |stream_res| gemini_it(stream_res, &my_pool, &acceptor)).unwrap());
+    }
+    if let Some(side_listener) = http_listener {
+        let my_pool = pool.clone();
+        info!("FROG IS ALSO SHOWING THE HOLE TO BROWSERS ON {}.", side_listener.local_addr().unwrap());
+        sides.push(accept_on_the_side("HTTP_ACCEPTOR", side_listener, stopping.clone(),
+                                      move |stream_res| browse_it(stream_res, &my_pool)).unwrap());
+    }
+    if let Some(side_listener) = finger_listener {
+        let my_pool = pool.clone();
+        info!("FROG IS ALSO HANDING OUT TIPS TO FINGER ON {}.", side_listener.local_addr().unwrap());
+        sides.push(accept_on_the_side("FINGER_ACCEPTOR", side_listener, stopping.clone(),
+                                      move |stream_res| finger_it(stream_res, &my_pool)).unwrap());
+    }
+    if let Some(side_listener) = metrics_listener {
+        let my_pool = pool.clone();
+        info!("FROG IS ALSO TELLING PROMETHEUS HOW IT IS DOING ON {}.", side_listener.local_addr().unwrap());
+        sides.push(accept_on_the_side("METRICS_ACCEPTOR", side_listener, stopping.clone(),
+                                      move |stream_res| measure_it(stream_res, &my_pool)).unwrap());
+    }
+
+    info!("FROG IS NOW PLAYING WITH GOPHERS");
+
+    for stream_res in listener.incoming() {
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+
+        if let Err(why) = gopher_it_ha_ha_puns(stream_res, &pool) {
+            error!("GOPHER FAILED TO POP OUT OF ITS BURROW: {}", why);
+        }
+    }
+
+    // Destroy this when it goes out of scope
+    drop(listener);
+
+    for side in sides {
+        let _ = side.join();
+    }
+    // Nobody else should be dispatching by now, but a side that would not stop may still be
+    let pool = match Arc::try_unwrap(pool) {
+        Ok(pool) => pool,
+        Err(_) => {
+            error!("FROG COULD NOT WAIT FOR THE LAST GOPHERS, SOMEONE IS STILL LETTING THEM IN");
+            return;
+        },
+    };
+
+    info!("FROG IS WAITING UP TO {}S FOR THE LAST GOPHERS.", config.grace.as_secs());
+    if pool.join_within(config.grace) {
+        info!("FROG HAS STOPPED PLAYING WITH GOPHERS");
+    } else {
+        warn!("FROG HAS STOPPED PLAYING WITH GOPHERS, EVEN THOUGH SOME WERE STILL BUSY");
+    }
+    info!("GOPHERS: {}.", shared_gopher.rate_limiter().counts());
+}
+
+// Answers the one gopher inetd picked up, then goes away
+fn serve_inetd(config: Config) {
+    // Anyone who could be told that logging failed is waiting for a gopher response
+    let _ = frog_log::init(&config.log);
+
+    let peer = inetd::peer();
+    let arrived = Instant::now();
+    let mut gopher = the_impl_ya_dummy::Gopher::once(config.ext_addr, config.frog_tips_api_key, &config.hole);
+    gopher.set_rate_limiter(RateLimiter::new(Limit::unlimited(), Limit::unlimited(), vec![], config.deny));
+
+    match peer {
+        Some(peer) => info!("A GOPHER HAS POPPED OUT OF ITS BURROW AT {}.", peer),
+        None => info!("A GOPHER HAS POPPED OUT OF A BURROW NOBODY KNOWS THE ADDRESS OF."),
+    }
+    let served = match inetd::respond(Arc::new(gopher), io::stdin(), io::stdout(), peer) {
+        Ok(served) => {
+            info!("A GOPHER HAS RETREATED INTO ITS BURROW ON GOOD TERMS. GOODBYE GOPHER.");
+            served
+        },
+        Err(why) => {
+            error!("A GOPHER HAS RETREATED INTO ITS BURROW ON BAD TERMS: {}", why);
+            Served::nothing(Outcome::Failed)
+        },
+    };
 
-        // Destroy this when it goes out of scope
-        drop(listener);
+    if let Some(ref access_log) = config.access_log {
+        match AccessLog::open(access_log.format, &access_log.file) {
+            Ok(access_log) => access_log.record(peer, "gopher", &served, arrived.elapsed()),
+            Err(why) => error!("FROG COULD NOT OPEN ITS ACCESS LOG {}: {}", access_log.file.display(), why),
+        }
+    }
+}
+
+// Written next to the dump and renamed over it, so a failed export leaves the last good one alone
+fn write_dump(file: &Path, tips: &[u8]) -> io::Result<()> {
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file.file_name().unwrap_or(OsStr::new("tips")));
+    temp_name.push(".tmp");
+    let temp = file.with_file_name(temp_name);
+
+    let res = File::create(&temp)
+        .and_then(|mut out| out.write_all(tips).and_then(|_| out.sync_all()))
+        .and_then(|_| fs::rename(&temp, file));
+    if res.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    res
+}
+
+fn export_tips(export: ExportConfig) {
+    let ExportConfig { api_url, frog_tips_api_key, file } = export;
+    let mut tips = Vec::new();
+    let res = the_impl_ya_dummy::export_tips(api_url, frog_tips_api_key, &mut tips)
+        .and_then(|count| write_dump(&file, &tips).map(|_| count).map_err(TipError::from));
+
+    match res {
+        Ok(count) => println!("FROG HAS EXPORTED {} TIPS TO {}.", count, file.display()),
+        Err(why) => {
+            println!("ERROR: COULD NOT EXPORT TIPS: {}", why);
+            process::exit(1);
+        },
+    }
+}
+
+fn main() {
+    cli::main(|command| match command {
+        Command::Serve(config) => serve(config),
+        Command::Inetd(config) => serve_inetd(config),
+        Command::ExportTips(export) => export_tips(export),
     });
 }
 
 
 #[cfg(test)]
 mod tests {
+    use std::env;
+    use std::fs::{self,File};
     use std::sync::Arc;
     use std::net::{TcpListener,TcpStream};
     use std::thread;
     use std::io::{Read,Write};
     use std::time::Duration;
 
+    use native_tls::TlsConnector;
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::x509::{X509,X509NameBuilder};
+
+    use super::cli::{TlsConfig,DEFAULT_WORKERS,DEFAULT_MAX_WAITING,DEFAULT_MAX_CONNECTIONS};
     use super::protocol::ExternalAddr;
-    use super::gopher_it_ha_ha_puns;
+    use super::mock_frog_tips;
+    use super::shared_buffer::Shared;
+    use super::{gopher_it_ha_ha_puns,gopher_it_securely,gemini_it,browse_it,measure_it,tls_acceptor,write_dump};
+    use super::gopher_pool::GopherPool;
     use super::the_impl_ya_dummy::Gopher;
+    use super::rate_limit::{Limit,RateLimiter};
+    use super::stats::StatsConfig;
+    use super::access_log::{self,AccessLog};
 
 
     macro_rules! t {
@@ -193,10 +769,22 @@ mod tests {
 
     const FROG_MODELS: &'static str = include_str!("../txt/FROG_MODELS");
 
+    // What gophers get sent, since none of the lines start with a period
+    fn frog_models_over_the_wire() -> String {
+        FROG_MODELS.lines().map(|line| format!("{}\r\n", line)).collect()
+    }
+
+    fn shared_gopher() -> Arc<Gopher> {
+        let ext_addr = ExternalAddr::new("127.0.0.1", 7777);
+        let api_key = "testing".to_string();
+        Arc::new(Gopher::new(ext_addr, api_key, &mock_frog_tips::hole_without_tips()))
+    }
+
     #[test]
     fn multiple_connections_dont_break() {
-        // THIS WILL KILL YOUR SYSTEM IF YOU SET IT HIGHER
-        const MAX: usize = 100;
+        // EVERY CONNECTION STAYS OPEN, WITH A THREAD OF ITS OWN, UNTIL THE LAST ONE IS MADE, AND BOTH
+        // ENDS COUNT AGAINST ulimit -n
+        const MAX: usize = 1000;
 
         // A reasonable amount of time to read and write to the connection
         const EXPECTED_WAIT_MS: u64 = 500;
@@ -207,20 +795,22 @@ mod tests {
             let shared_gopher = {
                 let ext_addr = ExternalAddr::new("127.0.0.1", 7777);
                 let api_key = "testing".to_string();
-                Arc::new(Gopher::new(ext_addr, api_key))
+                Arc::new(Gopher::new(ext_addr, api_key, &mock_frog_tips::hole_without_tips()))
             };
+            let pool = t!(GopherPool::new(shared_gopher, DEFAULT_WORKERS, DEFAULT_MAX_WAITING, DEFAULT_MAX_CONNECTIONS));
 
             let acceptor = t!(TcpListener::bind(addr));
             let mut conn = vec![];
 
             for stream_res in acceptor.incoming().take(MAX) {
-                conn.push(gopher_it_ha_ha_puns(stream_res, &shared_gopher));
+                conn.push(gopher_it_ha_ha_puns(stream_res, &pool));
             }
 
             for c in conn {
                 assert!(c.is_ok());
-                c.unwrap().join().ok().unwrap();
             }
+
+            pool.join();
         });
 
         // Wait a bit for the server to start
@@ -248,9 +838,288 @@ mod tests {
                 assert!(stream.read(&mut buffer).unwrap() > 0);
 
                 let resp = ::std::str::from_utf8(&buffer).unwrap();
-                assert!(FROG_MODELS.starts_with(resp));
+                assert!(frog_models_over_the_wire().starts_with(resp));
             });
             t.join().ok().unwrap();
         }
     }
+
+    #[test]
+    fn busy_frogs_turn_gophers_away() {
+        let acceptor = t!(TcpListener::bind("127.0.0.1:0"));
+        let addr = t!(acceptor.local_addr());
+        let pool = t!(GopherPool::new(shared_gopher(), 1, 1, 2));
+
+        // Saying nothing costs no worker, only room
+        let mut first = t!(TcpStream::connect(addr));
+        t!(pool.dispatch(t!(acceptor.accept()).0));
+
+        let mut second = t!(TcpStream::connect(addr));
+        t!(pool.dispatch(t!(acceptor.accept()).0));
+
+        let mut third = t!(TcpStream::connect(addr));
+        t!(pool.dispatch(t!(acceptor.accept()).0));
+
+        let mut resp = String::new();
+        t!(third.read_to_string(&mut resp));
+        assert_eq!(resp, "3FROG IS BUSY. PLEASE TRY AGAIN LATER.\r\n.\r\n");
+
+        for stream in vec![&mut first, &mut second] {
+            t!(write!(stream, "/DOCS/FROG_MODELS\r\n"));
+            let mut resp = String::new();
+            t!(stream.read_to_string(&mut resp));
+            assert!(resp.starts_with(&frog_models_over_the_wire()));
+        }
+
+        pool.join();
+    }
+
+    #[test]
+    fn every_gopher_gets_an_access_log_line() {
+        let acceptor = t!(TcpListener::bind("127.0.0.1:0"));
+        let addr = t!(acceptor.local_addr());
+        let log = Shared::new();
+        let mut pool = t!(GopherPool::new(shared_gopher(), 1, 1, 1));
+        pool.set_access_log(AccessLog::new(access_log::Format::Common, Box::new(log.clone())));
+
+        let mut stream = t!(TcpStream::connect(addr));
+        t!(pool.dispatch(t!(acceptor.accept()).0));
+        t!(write!(stream, "/DOCS/FROG_MODELS\r\n"));
+        let mut resp = Vec::new();
+        t!(stream.read_to_end(&mut resp));
+        pool.join();
+
+        let logged = log.contents();
+        assert_eq!(logged.lines().count(), 1);
+        assert!(logged.starts_with("127.0.0.1 - - ["));
+        assert!(logged.contains(&format!("\"0 /DOCS/FROG_MODELS GOPHER\" 200 {} ", resp.len())));
+    }
+
+    #[test]
+    fn chatty_gophers_are_asked_to_slow_down() {
+        let acceptor = t!(TcpListener::bind("127.0.0.1:0"));
+        let addr = t!(acceptor.local_addr());
+        let mut gopher = Gopher::new(ExternalAddr::new("127.0.0.1", 70), String::new(), &mock_frog_tips::hole_without_tips());
+        gopher.set_rate_limiter(RateLimiter::new(Limit { per_minute: 1 }, Limit::unlimited(), vec![], vec![]));
+        let pool = t!(GopherPool::new(Arc::new(gopher), 1, 1, 1));
+
+        let mut first = t!(TcpStream::connect(addr));
+        t!(pool.dispatch(t!(acceptor.accept()).0));
+        t!(write!(first, "/DOCS/FROG_MODELS\r\n"));
+        let mut resp = String::new();
+        t!(first.read_to_string(&mut resp));
+        assert!(resp.starts_with(&frog_models_over_the_wire()));
+
+        let mut second = t!(TcpStream::connect(addr));
+        t!(pool.dispatch(t!(acceptor.accept()).0));
+        let mut resp = String::new();
+        t!(second.read_to_string(&mut resp));
+        assert_eq!(resp, "3SLOW DOWN, FRIEND.\r\n.\r\n");
+
+        pool.join();
+    }
+
+    #[test]
+    fn prometheus_sees_what_gophers_asked_for() {
+        let acceptor = t!(TcpListener::bind("127.0.0.1:0"));
+        let addr = t!(acceptor.local_addr());
+        let gopher = Gopher::new(ExternalAddr::new("127.0.0.1", 70), String::new(), &mock_frog_tips::hole_without_tips());
+        let pool = t!(GopherPool::new(Arc::new(gopher), 1, 1, 1));
+
+        let mut stream = t!(TcpStream::connect(addr));
+        t!(gopher_it_ha_ha_puns(acceptor.accept().map(|(stream, _)| stream), &pool));
+        t!(write!(stream, "/DOCS/FROG_MODELS\r\n"));
+        t!(stream.read_to_string(&mut String::new()));
+
+        let mut scraper = t!(TcpStream::connect(addr));
+        t!(write!(scraper, "GET /metrics HTTP/1.1\r\n\r\n"));
+        t!(measure_it(acceptor.accept().map(|(stream, _)| stream), &pool));
+        let mut resp = String::new();
+        t!(scraper.read_to_string(&mut resp));
+        assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(resp.contains("frog_requests_total{selector=\"/DOCS/FROG_MODELS\",source=\"text\"} 1\n"));
+        assert!(resp.contains("frog_gophers_max_open 1\n"));
+        assert!(resp.contains("frog_gophers_max_waiting 1\n"));
+
+        pool.join();
+    }
+
+    #[test]
+    fn only_trusted_gophers_find_the_stats() {
+        fn ask_for_stats(allow: &str) -> String {
+            let acceptor = t!(TcpListener::bind("127.0.0.1:0"));
+            let mut gopher = Gopher::new(ExternalAddr::new("127.0.0.1", 70), String::new(), &mock_frog_tips::hole_without_tips());
+            gopher.set_stats(StatsConfig {
+                selector: "/SECRET/STATS".to_string(),
+                allow: vec![t!(allow.parse())],
+            });
+            let pool = t!(GopherPool::new(Arc::new(gopher), 1, 1, 1));
+
+            let mut stream = t!(TcpStream::connect(t!(acceptor.local_addr())));
+            t!(gopher_it_ha_ha_puns(acceptor.accept().map(|(stream, _)| stream), &pool));
+            t!(write!(stream, "/SECRET/STATS\r\n"));
+            let mut resp = String::new();
+            t!(stream.read_to_string(&mut resp));
+            pool.join();
+            resp
+        }
+
+        assert!(ask_for_stats("127.0.0.0/8").starts_with("iFROG STATS.\t"));
+        assert!(ask_for_stats("192.0.2.0/24").starts_with("3/SECRET/STATS NOT FOUND\r\n"));
+    }
+
+    #[test]
+    fn joining_gives_up_on_slow_gophers() {
+        let acceptor = t!(TcpListener::bind("127.0.0.1:0"));
+        let addr = t!(acceptor.local_addr());
+
+        let pool = t!(GopherPool::new(shared_gopher(), 1, 1, 1));
+        assert!(pool.join_within(Duration::from_secs(1)));
+
+        // This one never says a word
+        let pool = t!(GopherPool::new(shared_gopher(), 1, 1, 1));
+        let _silent = t!(TcpStream::connect(addr));
+        t!(pool.dispatch(t!(acceptor.accept()).0));
+        assert!(!pool.join_within(Duration::from_millis(100)));
+    }
+
+    // A certificate nobody would trust, made up on the spot
+    fn self_signed(name: &str) -> TlsConfig {
+        let key = t!(PKey::from_rsa(t!(Rsa::generate(2048))));
+
+        let mut subject = t!(X509NameBuilder::new());
+        t!(subject.append_entry_by_text("CN", "localhost"));
+        let subject = subject.build();
+
+        let serial = t!(t!(BigNum::from_u32(1)).to_asn1_integer());
+        let not_before = t!(Asn1Time::days_from_now(0));
+        let not_after = t!(Asn1Time::days_from_now(1));
+
+        let mut cert = t!(X509::builder());
+        t!(cert.set_version(2));
+        t!(cert.set_serial_number(&serial));
+        t!(cert.set_subject_name(&subject));
+        t!(cert.set_issuer_name(&subject));
+        t!(cert.set_pubkey(&key));
+        t!(cert.set_not_before(&not_before));
+        t!(cert.set_not_after(&not_after));
+        t!(cert.sign(&key, MessageDigest::sha256()));
+        let cert = cert.build();
+
+        let dir = env::temp_dir().join(format!("frog_gopher_{}", name));
+        t!(fs::create_dir_all(&dir));
+        t!(t!(File::create(dir.join("cert.pem"))).write_all(&t!(cert.to_pem())));
+        t!(t!(File::create(dir.join("key.pem"))).write_all(&t!(key.private_key_to_pem_pkcs8())));
+
+        TlsConfig {
+            cert: dir.join("cert.pem"),
+            key: dir.join("key.pem"),
+        }
+    }
+
+    #[test]
+    fn tls_gophers_get_the_same_menus() {
+        let tls = t!(tls_acceptor(&self_signed("tls")));
+        let acceptor = t!(TcpListener::bind("127.0.0.1:0"));
+        let addr = t!(acceptor.local_addr());
+        let pool = t!(GopherPool::new(shared_gopher(), 1, 1, 1));
+
+        let stream = t!(TcpStream::connect(addr));
+        t!(gopher_it_securely(acceptor.accept().map(|(stream, _)| stream), &pool, &tls));
+
+        let connector = t!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+        let mut stream = t!(connector.connect("localhost", stream));
+        t!(write!(stream, "/DOCS\r\n"));
+        let mut resp = String::new();
+        t!(stream.read_to_string(&mut resp));
+
+        // Links still point at the plain address frog was told about
+        assert!(resp.contains("\t/DOCS/FROG_MODELS\t127.0.0.1\t7777\t+\r\n"));
+        assert!(resp.ends_with(".\r\n"));
+
+        pool.join();
+    }
+
+    #[test]
+    fn gemini_gets_the_same_menus() {
+        let tls = t!(tls_acceptor(&self_signed("gemini")));
+        let acceptor = t!(TcpListener::bind("127.0.0.1:0"));
+        let addr = t!(acceptor.local_addr());
+        let pool = t!(GopherPool::new(shared_gopher(), 1, 1, DEFAULT_MAX_CONNECTIONS));
+        let connector = t!(TlsConnector::builder().danger_accept_invalid_certs(true).build());
+
+        let ask = |url: &str| {
+            let stream = t!(TcpStream::connect(addr));
+            t!(gemini_it(acceptor.accept().map(|(stream, _)| stream), &pool, &tls));
+            let mut stream = t!(connector.connect("localhost", stream));
+            t!(write!(stream, "{}\r\n", url));
+            let mut resp = String::new();
+            t!(stream.read_to_string(&mut resp));
+            resp
+        };
+
+        let menu = ask("gemini://localhost/1/DOCS");
+        assert!(menu.starts_with("20 text/gemini; charset=utf-8\r\n"));
+        assert!(menu.contains("\r\n=> /0/DOCS/FROG_MODELS "));
+
+        let text = ask("gemini://localhost/0/DOCS/FROG_MODELS");
+        assert_eq!(text, format!("20 text/plain; charset=utf-8\r\n{}", FROG_MODELS));
+
+        assert!(ask("gemini://localhost/0/NOWHERE").starts_with("51 "));
+
+        pool.join();
+    }
+
+    #[test]
+    fn browsers_get_the_same_menus() {
+        let acceptor = t!(TcpListener::bind("127.0.0.1:0"));
+        let addr = t!(acceptor.local_addr());
+        let pool = t!(GopherPool::new(shared_gopher(), 1, 1, DEFAULT_MAX_CONNECTIONS));
+
+        let ask = |path: &str| {
+            let mut stream = t!(TcpStream::connect(addr));
+            t!(browse_it(acceptor.accept().map(|(stream, _)| stream), &pool));
+            t!(write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path));
+            let mut resp = String::new();
+            t!(stream.read_to_string(&mut resp));
+            resp
+        };
+
+        let menu = ask("/1/DOCS");
+        assert!(menu.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(menu.contains("<li><a href=\"/0/DOCS/FROG_MODELS\">"));
+
+        let root = ask("/");
+        assert!(root.contains("<form action=\"/7/GENUINEFROG\">"));
+
+        let text = ask("/0/DOCS/FROG_MODELS");
+        assert!(text.contains("<pre>"));
+
+        assert!(ask("/0/NOWHERE").starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        pool.join();
+    }
+
+    #[test]
+    fn tls_wants_a_real_key() {
+        let mut tls = self_signed("tls_bad_key");
+        tls.key = tls.cert.clone();
+        assert!(tls_acceptor(&tls).is_err());
+    }
+
+    #[test]
+    fn dumps_are_replaced_whole() {
+        let dir = env::temp_dir().join("frog_gopher_dump_replaced");
+        let _ = fs::remove_dir_all(&dir);
+        t!(fs::create_dir_all(&dir));
+        let file = dir.join("tips.jsonl");
+        t!(t!(File::create(&file)).write_all(b"OLD TIPS\n"));
+
+        t!(write_dump(&file, b"NEW TIPS\n"));
+        let mut tips = String::new();
+        t!(t!(File::open(&file)).read_to_string(&mut tips));
+        assert_eq!(tips, "NEW TIPS\n");
+        // Nothing is left lying around
+        assert_eq!(t!(fs::read_dir(&dir)).count(), 1);
+    }
 }
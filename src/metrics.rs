@@ -0,0 +1,365 @@
+// Counts of what frog has been up to, for Prometheus to scrape off a local port.
+//
+// Requests are counted by selector and the kind of source that answered, errors by what went
+// wrong, and how long things took goes into histograms. Searches, tip refreshes and the latest
+// errors are kept for the stats menu.
+
+use std::collections::{BTreeMap,VecDeque};
+use std::io::{self,Read,Write};
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::time::{Duration,Instant};
+
+use time;
+
+
+// Upper bounds of the histogram buckets, in seconds
+static BUCKETS: &'static [f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// Past this many selectors, new ones are counted together so nobody can fill frog's memory with junk
+const MAX_SELECTORS: usize = 1000;
+static OTHER_SELECTORS: &'static str = "OTHER";
+
+// How many errors to remember for the stats
+const RECENT_ERRORS: usize = 10;
+
+// Nobody needs more than this to ask for metrics
+const MAX_HEAD_LEN: usize = 8192;
+
+// Prometheus asks right away, so a scraper that takes longer than this, all told, is not one
+const SCRAPE_TIMEOUT_SECS: u64 = 2;
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[derive(Clone,Default)]
+struct Histogram {
+    // How many observations fell at or under each bucket's bound, not counting lower buckets
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, took: Duration) {
+        let secs = seconds(took);
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; BUCKETS.len()];
+        }
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| secs <= bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, label: &str, value: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(self.buckets.iter()) {
+            cumulative += *count;
+            out.push_str(&format!("{}_bucket{{{}=\"{}\",le=\"{}\"}} {}\n", name, label, escape(value), bound, cumulative));
+        }
+        out.push_str(&format!("{}_bucket{{{}=\"{}\",le=\"+Inf\"}} {}\n", name, label, escape(value), self.count));
+        out.push_str(&format!("{}_sum{{{}=\"{}\"}} {}\n", name, label, escape(value), self.sum));
+        out.push_str(&format!("{}_count{{{}=\"{}\"}} {}\n", name, label, escape(value), self.count));
+    }
+}
+
+pub struct Metrics {
+    // By selector, then the kind of source that answered
+    requests: Mutex<BTreeMap<(String, &'static str), u64>>,
+    protocol_errors: Mutex<BTreeMap<&'static str, u64>>,
+    tip_errors: Mutex<BTreeMap<&'static str, u64>>,
+    // By frontend
+    respond_seconds: Mutex<BTreeMap<&'static str, Histogram>>,
+    // By what was asked of frog.tips
+    tip_api_seconds: Mutex<BTreeMap<&'static str, Histogram>>,
+    // By frontend
+    connections: Mutex<BTreeMap<&'static str, i64>>,
+    // By the kind of source searched, then the terms
+    searches: Mutex<BTreeMap<(&'static str, String), u64>>,
+    // When tips were last refreshed, and how many there were
+    tips_refreshed: Mutex<Option<(Instant, usize)>>,
+    // The latest last, with when they happened
+    recent_errors: Mutex<VecDeque<(String, String)>>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics {
+            requests: Mutex::new(BTreeMap::new()),
+            protocol_errors: Mutex::new(BTreeMap::new()),
+            tip_errors: Mutex::new(BTreeMap::new()),
+            respond_seconds: Mutex::new(BTreeMap::new()),
+            tip_api_seconds: Mutex::new(BTreeMap::new()),
+            connections: Mutex::new(BTreeMap::new()),
+            searches: Mutex::new(BTreeMap::new()),
+            tips_refreshed: Mutex::new(None),
+            recent_errors: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn request(&self, selector: &str, source: &'static str) {
+        let mut requests = self.requests.lock().unwrap();
+        let mut key = (selector.to_string(), source);
+        if requests.len() >= MAX_SELECTORS && !requests.contains_key(&key) {
+            key.0 = OTHER_SELECTORS.to_string();
+        }
+        *requests.entry(key).or_insert(0) += 1;
+    }
+
+    // Searches are only for the stats, so they don't end up as labels
+    pub fn searched(&self, source: &'static str, terms: &str) {
+        let mut searches = self.searches.lock().unwrap();
+        let mut key = (source, terms.trim().to_uppercase());
+        if searches.len() >= MAX_SELECTORS && !searches.contains_key(&key) {
+            key.1 = OTHER_SELECTORS.to_string();
+        }
+        *searches.entry(key).or_insert(0) += 1;
+    }
+
+    pub fn protocol_error(&self, variant: &'static str, why: &str) {
+        *self.protocol_errors.lock().unwrap().entry(variant).or_insert(0) += 1;
+        self.remember_error(why);
+    }
+
+    pub fn tip_error(&self, variant: &'static str, why: &str) {
+        *self.tip_errors.lock().unwrap().entry(variant).or_insert(0) += 1;
+        self.remember_error(why);
+    }
+
+    pub fn remember_error(&self, why: &str) {
+        let mut recent_errors = self.recent_errors.lock().unwrap();
+        if recent_errors.len() >= RECENT_ERRORS {
+            recent_errors.pop_front();
+        }
+        recent_errors.push_back((time::now_utc().rfc3339().to_string(), why.to_string()));
+    }
+
+    pub fn tips_refreshed(&self, count: usize) {
+        *self.tips_refreshed.lock().unwrap() = Some((Instant::now(), count));
+    }
+
+    pub fn responded(&self, frontend: &'static str, took: Duration) {
+        self.respond_seconds.lock().unwrap().entry(frontend).or_insert_with(Histogram::default).observe(took);
+    }
+
+    pub fn asked_frog_tips(&self, call: &'static str, took: Duration) {
+        self.tip_api_seconds.lock().unwrap().entry(call).or_insert_with(Histogram::default).observe(took);
+    }
+
+    pub fn connection_opened(&self, frontend: &'static str) {
+        *self.connections.lock().unwrap().entry(frontend).or_insert(0) += 1;
+    }
+
+    pub fn connection_closed(&self, frontend: &'static str) {
+        *self.connections.lock().unwrap().entry(frontend).or_insert(0) -= 1;
+    }
+
+    pub fn total_requests(&self) -> u64 {
+        self.requests.lock().unwrap().values().sum()
+    }
+
+    // The most asked for selectors, whichever source had them
+    pub fn top_selectors(&self, n: usize) -> Vec<(String, u64)> {
+        let mut by_selector = BTreeMap::new();
+        for (&(ref selector, _), count) in self.requests.lock().unwrap().iter() {
+            *by_selector.entry(selector.clone()).or_insert(0) += *count;
+        }
+        top(by_selector.into_iter(), n)
+    }
+
+    pub fn top_searches(&self, source: &'static str, n: usize) -> Vec<(String, u64)> {
+        let searches = self.searches.lock().unwrap();
+        top(searches.iter()
+                    .filter(|&(&(kind, _), _)| kind == source)
+                    .map(|(&(_, ref terms), count)| (terms.clone(), *count)), n)
+    }
+
+    pub fn tips_last_refreshed(&self) -> Option<(Duration, usize)> {
+        self.tips_refreshed.lock().unwrap().map(|(when, count)| (when.elapsed(), count))
+    }
+
+    pub fn recent_errors(&self) -> Vec<(String, String)> {
+        self.recent_errors.lock().unwrap().iter().cloned().collect()
+    }
+
+    // Everything, in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP frog_requests_total Requests by selector and the kind of source that answered.\n");
+        out.push_str("# TYPE frog_requests_total counter\n");
+        for (&(ref selector, source), count) in self.requests.lock().unwrap().iter() {
+            out.push_str(&format!("frog_requests_total{{selector=\"{}\",source=\"{}\"}} {}\n", escape(selector), source, count));
+        }
+
+        out.push_str("# HELP frog_protocol_errors_total Requests that broke the protocol, by what went wrong.\n");
+        out.push_str("# TYPE frog_protocol_errors_total counter\n");
+        for (variant, count) in self.protocol_errors.lock().unwrap().iter() {
+            out.push_str(&format!("frog_protocol_errors_total{{error=\"{}\"}} {}\n", variant, count));
+        }
+
+        out.push_str("# HELP frog_tip_errors_total Failures to get tips, by what went wrong.\n");
+        out.push_str("# TYPE frog_tip_errors_total counter\n");
+        for (variant, count) in self.tip_errors.lock().unwrap().iter() {
+            out.push_str(&format!("frog_tip_errors_total{{error=\"{}\"}} {}\n", variant, count));
+        }
+
+        out.push_str("# HELP frog_respond_seconds How long answering took, from reading the request to the last byte.\n");
+        out.push_str("# TYPE frog_respond_seconds histogram\n");
+        for (frontend, histogram) in self.respond_seconds.lock().unwrap().iter() {
+            histogram.render(&mut out, "frog_respond_seconds", "frontend", frontend);
+        }
+
+        out.push_str("# HELP frog_tip_api_seconds How long frog.tips took to answer.\n");
+        out.push_str("# TYPE frog_tip_api_seconds histogram\n");
+        for (call, histogram) in self.tip_api_seconds.lock().unwrap().iter() {
+            histogram.render(&mut out, "frog_tip_api_seconds", "call", call);
+        }
+
+        out.push_str("# HELP frog_connections Connections being answered right now.\n");
+        out.push_str("# TYPE frog_connections gauge\n");
+        for (frontend, count) in self.connections.lock().unwrap().iter() {
+            out.push_str(&format!("frog_connections{{frontend=\"{}\"}} {}\n", frontend, count));
+        }
+
+        out
+    }
+}
+
+// The n biggest counts, ties in the order they came
+fn top<I: Iterator<Item=(String, u64)>>(counts: I, n: usize) -> Vec<(String, u64)> {
+    let mut counts: Vec<(String, u64)> = counts.collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1));
+    counts.truncate(n);
+    counts
+}
+
+// Answers whatever was asked with the metrics, since there is nothing else here
+pub fn respond(mut stream: TcpStream, metrics: &str) -> io::Result<()> {
+    let started = Instant::now();
+    let timeout = Duration::from_secs(SCRAPE_TIMEOUT_SECS);
+    try!(stream.set_write_timeout(Some(timeout)));
+
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && !head.windows(2).any(|w| w == b"\n\n") {
+        // A byte at a time must not keep the next scrape waiting any longer
+        let elapsed = started.elapsed();
+        if elapsed >= timeout {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "the scraper took too long to ask"));
+        }
+        try!(stream.set_read_timeout(Some(timeout - elapsed)));
+        let len = try!(stream.read(&mut buf));
+        if len == 0 || head.len() > MAX_HEAD_LEN {
+            break;
+        }
+        head.extend_from_slice(&buf[..len]);
+    }
+
+    try!(write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                metrics.len()));
+    stream.write_all(metrics.as_bytes())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self,Write};
+    use std::net::{TcpListener,TcpStream};
+    use std::thread;
+    use std::time::{Duration,Instant};
+
+    use super::Metrics;
+
+
+    #[test]
+    fn metrics_are_prometheus_text() {
+        let metrics = Metrics::new();
+        metrics.request("/DOCS/FROG_MODELS", "text");
+        metrics.request("/DOCS/FROG_MODELS", "text");
+        metrics.request("/\"SNEAKY\"", "none");
+        metrics.tip_error("status", "Unexpected status: 500 Internal Server Error");
+        metrics.protocol_error("line_too_big", "Line too big error");
+        metrics.responded("gopher", Duration::from_millis(20));
+        metrics.responded("gopher", Duration::from_secs(60));
+        metrics.connection_opened("http");
+
+        let text = metrics.render();
+        assert!(text.contains("frog_requests_total{selector=\"/DOCS/FROG_MODELS\",source=\"text\"} 2\n"));
+        assert!(text.contains("frog_requests_total{selector=\"/\\\"SNEAKY\\\"\",source=\"none\"} 1\n"));
+        assert!(text.contains("frog_tip_errors_total{error=\"status\"} 1\n"));
+        assert!(text.contains("frog_protocol_errors_total{error=\"line_too_big\"} 1\n"));
+        assert!(text.contains("frog_respond_seconds_bucket{frontend=\"gopher\",le=\"0.01\"} 0\n"));
+        assert!(text.contains("frog_respond_seconds_bucket{frontend=\"gopher\",le=\"0.025\"} 1\n"));
+        assert!(text.contains("frog_respond_seconds_bucket{frontend=\"gopher\",le=\"10\"} 1\n"));
+        assert!(text.contains("frog_respond_seconds_bucket{frontend=\"gopher\",le=\"+Inf\"} 2\n"));
+        assert!(text.contains("frog_respond_seconds_count{frontend=\"gopher\"} 2\n"));
+        assert!(text.contains("frog_connections{frontend=\"http\"} 1\n"));
+    }
+
+    #[test]
+    fn stats_add_up() {
+        let metrics = Metrics::new();
+        for &(selector, source, times) in &[("/TIP/SEARCH", "tips", 3), ("/DOCS", "menu", 1), ("/TIP", "menu", 2)] {
+            for _ in 0..times {
+                metrics.request(selector, source);
+            }
+        }
+        metrics.request("/TIP/SEARCH", "rate_limit");
+        assert_eq!(metrics.total_requests(), 7);
+        assert_eq!(metrics.top_selectors(2), vec![("/TIP/SEARCH".to_string(), 4), ("/TIP".to_string(), 2)]);
+
+        metrics.searched("tips", "toy");
+        metrics.searched("tips", " TOY ");
+        metrics.searched("genuine_frog", "FC-9301-00042-9");
+        assert_eq!(metrics.top_searches("tips", 5), vec![("TOY".to_string(), 2)]);
+
+        for i in 0..super::RECENT_ERRORS + 1 {
+            metrics.remember_error(&format!("ERROR {}", i));
+        }
+        let errors = metrics.recent_errors();
+        assert_eq!(errors.len(), super::RECENT_ERRORS);
+        assert_eq!(errors.last().unwrap().1, format!("ERROR {}", super::RECENT_ERRORS));
+    }
+
+    #[test]
+    fn junk_selectors_are_lumped_together() {
+        let metrics = Metrics::new();
+        for i in 0..super::MAX_SELECTORS + 10 {
+            metrics.request(&format!("/JUNK/{}", i), "none");
+        }
+        assert!(metrics.render().contains("frog_requests_total{selector=\"OTHER\",source=\"none\"} 10\n"));
+    }
+
+    #[test]
+    fn dawdling_scrapers_are_cut_off() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut scraper = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        // Never quiet for long, but never done asking either
+        let dawdler = thread::spawn(move || {
+            for _ in 0..8 {
+                if write!(scraper, "G").is_err() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(500));
+            }
+        });
+
+        let started = Instant::now();
+        match super::respond(stream, "") {
+            Err(ref why) if why.kind() == io::ErrorKind::TimedOut || why.kind() == io::ErrorKind::WouldBlock => {},
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(super::SCRAPE_TIMEOUT_SECS + 1));
+        dawdler.join().unwrap();
+    }
+}
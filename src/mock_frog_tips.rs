@@ -0,0 +1,129 @@
+// A pretend frog.tips API, so tips can be tested without bothering the real one
+
+use std::io::Read;
+
+use hyper::server::{Server,Request,Response,Listening};
+use hyper::status::StatusCode;
+use hyper::method::Method;
+use hyper::uri::RequestUri;
+use hyper::header::Authorization;
+
+use hole_config::{HoleConfig,SourceConfig};
+
+
+pub static API_KEY: &'static str = "TESTING";
+
+pub struct Reply {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl Reply {
+    pub fn ok<S: Into<String>>(body: S) -> Reply {
+        Reply {
+            status: StatusCode::Ok,
+            body: body.into(),
+        }
+    }
+
+    pub fn status(status: StatusCode) -> Reply {
+        Reply {
+            status: status,
+            body: String::new(),
+        }
+    }
+}
+
+// The hole built into the server, minus its tips, for tests that have no use for them
+pub fn hole_without_tips() -> HoleConfig {
+    fn without_tips(sources: Vec<SourceConfig>) -> Vec<SourceConfig> {
+        sources.into_iter().filter_map(|source| match source {
+            SourceConfig::Tips {..} => None,
+            SourceConfig::Menu {path, desc, sources} => Some(SourceConfig::Menu {
+                path: path,
+                desc: desc,
+                sources: without_tips(sources),
+            }),
+            source => Some(source),
+        }).collect()
+    }
+
+    let hole = HoleConfig::builtin();
+    HoleConfig {
+        sources: without_tips(hole.sources),
+        aliases: hole.aliases,
+    }
+}
+
+pub fn tip_json(number: u64, tweeted: u64, tip: &str) -> String {
+    format!("{{\"approved\":true,\"moderated\":true,\"tweeted\":{},\"number\":{},\"tip\":\"{}\"}}",
+            tweeted, number, tip)
+}
+
+pub fn search_json(tips: &[(u64, u64, &str)]) -> String {
+    let results: Vec<String> = tips.iter()
+                                   .map(|&(number, tweeted, tip)| tip_json(number, tweeted, tip))
+                                   .collect();
+    format!("{{\"results\":[{}]}}", results.join(","))
+}
+
+// The tips every mock knows about unless told otherwise
+pub fn canned(method: &Method, path: &str, _: &str) -> Reply {
+    match (method, path) {
+        (&Method::Post, "/api/2/tips/search") => Reply::ok(search_json(&[
+            (1, 100, "FROG IS NOT A TOY."),
+            (2, 300, "FROG IS A TOY."),
+            (3, 200, "NEVER MICROWAVE FROG."),
+        ])),
+        (&Method::Get, "/api/2/tips/1") => Reply::ok(tip_json(1, 100, "FROG IS NOT A TOY.")),
+        (&Method::Get, "/api/2/tips/2") => Reply::ok(tip_json(2, 300, "FROG IS A TOY.")),
+        (&Method::Get, "/api/2/tips/3") => Reply::ok(tip_json(3, 200, "NEVER MICROWAVE FROG.")),
+        // Approved but never tweeted, so it only turns up when asked for by number
+        (&Method::Get, "/api/2/tips/4") => Reply::ok(tip_json(4, 0, "FROG WAS NEVER TWEETED.")),
+        _ => Reply::status(StatusCode::NotFound),
+    }
+}
+
+pub struct MockFrogTips {
+    listening: Listening,
+}
+
+impl MockFrogTips {
+    // Routes get the method, the path and the request body
+    pub fn start<F>(route: F) -> MockFrogTips
+        where F: Fn(&Method, &str, &str) -> Reply + Send + Sync + 'static {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let listening = server.handle(move |mut req: Request, mut res: Response| {
+            let path = match req.uri {
+                RequestUri::AbsolutePath(ref path) => path.clone(),
+                _ => String::new(),
+            };
+
+            let reply = if req.headers.get::<Authorization<String>>().map(|a| a.0.as_str()) != Some(API_KEY) {
+                Reply::status(StatusCode::Unauthorized)
+            } else {
+                let mut body = String::new();
+                let _ = req.read_to_string(&mut body);
+                route(&req.method, &path, &body)
+            };
+
+            *res.status_mut() = reply.status;
+            let _ = res.send(reply.body.as_bytes());
+        }).unwrap();
+
+        MockFrogTips {
+            listening: listening,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}/api/2", self.listening.socket)
+    }
+}
+
+impl Drop for MockFrogTips {
+    fn drop(&mut self) {
+        // Dropping a listener waits forever for it to stop, so just walk away
+        let _ = self.listening.close();
+    }
+}
@@ -1,12 +1,15 @@
 
 use std::io;
-use std::io::{Read,Write};
-use std::net::TcpStream;
+use std::io::Write;
 use std::string::FromUtf8Error;
 use std::error;
 use std::fmt;
 
+use futures::{Async,Future,Poll};
 use hyper::Url;
+use tokio::io::AsyncRead;
+
+use rate_limit::SLOW_DOWN;
 
 
 #[derive(Clone,Debug,Eq,PartialEq)]
@@ -52,35 +55,204 @@ impl fmt::Display for Path {
 
 // Reading
 
-#[derive(Debug)]
+#[derive(Debug,Eq,PartialEq)]
 pub enum Selector {
     Path(Path),
     Empty,
 }
 
+// What a Gopher+ client wants, taken from the last tab-delimited field
+#[derive(Clone,Debug,Eq,PartialEq)]
+pub enum PlusRequest {
+    // `+`, optionally followed by the name of a view
+    Item(Option<String>),
+    // `!`
+    Attributes,
+    // `$`
+    DirectoryAttributes,
+}
+
+impl PlusRequest {
+    fn parse(field: &str) -> Option<PlusRequest> {
+        let mut chars = field.chars();
+        match chars.next() {
+            Some('+') => {
+                let view = chars.as_str();
+                if view.is_empty() {
+                    Some(PlusRequest::Item(None))
+                } else {
+                    Some(PlusRequest::Item(Some(view.to_string())))
+                }
+            },
+            Some('!') => Some(PlusRequest::Attributes),
+            Some('$') => Some(PlusRequest::DirectoryAttributes),
+            _ => None,
+        }
+    }
+
+    // For a field that could just as well be search terms. Only takes what a Gopher+ client
+    // sends on its own: a bare +, ! or $, their attribute forms, or + with a MIME view.
+    fn parse_alone(field: &str) -> Option<PlusRequest> {
+        let mut chars = field.chars();
+        let first = chars.next();
+        let rest = chars.as_str();
+        let plus_shaped = match first {
+            Some('+') => rest.is_empty() || rest.contains('/'),
+            Some('!') | Some('$') => rest.is_empty() || rest.starts_with('+'),
+            _ => false,
+        };
+        if plus_shaped { PlusRequest::parse(field) } else { None }
+    }
+}
+
 // Writing
 
 pub enum Selected<'a> {
     Error(Box<String>),
+    // The source had it, but could not get at it just now
+    Failed(Box<String>),
+    // The gopher has been asking too often to get it
+    SlowDown,
     // Newline-delimited lines to write
     Text(Box<String>),
+    // Bytes to write as they are, with nothing after them
+    Binary(Box<Vec<u8>>),
     TempMenu(Box<Menu>),
     ForeverMenu(&'a Menu),
 }
 
 pub trait Menu {
     fn items(&self) -> Vec<MenuItem>;
+
+    // How big an item is, if the menu can say without fetching it
+    fn size(&self, _: &MenuItem) -> Option<usize> {
+        None
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone,Debug)]
 pub enum MenuItem {
     // Other types are not supported
     Text {path: Path, desc: String},
+    Directory {path: Path, desc: String},
     Info {desc: String},
     JohnGoerzenUrl {url: Url, desc: String},
     Search {path: Path, desc: String},
+    // Files the client has to make sense of on its own
+    Binary {path: Path, desc: String},
+    Gif {path: Path, desc: String},
+    Image {path: Path, desc: String},
+    Archive {path: Path, desc: String},
+    Sound {path: Path, desc: String},
+    // Anything on another server, which is left to say what it is
+    Elsewhere {item_type: char, path: Path, desc: String, host: String, port: u16},
 }
 
+impl MenuItem {
+    // Any of the binary items, by type
+    pub fn binary<S: Into<String>>(item_type: char, path: Path, desc: S) -> Option<MenuItem> {
+        let desc = desc.into();
+        match item_type {
+            '9' => Some(MenuItem::Binary {path: path, desc: desc}),
+            'g' => Some(MenuItem::Gif {path: path, desc: desc}),
+            'I' => Some(MenuItem::Image {path: path, desc: desc}),
+            '5' => Some(MenuItem::Archive {path: path, desc: desc}),
+            's' => Some(MenuItem::Sound {path: path, desc: desc}),
+            _ => None,
+        }
+    }
+
+    pub fn item_type(&self) -> char {
+        match self {
+            &MenuItem::Text {..} => '0',
+            &MenuItem::Directory {..} => '1',
+            &MenuItem::Info {..} => 'i',
+            &MenuItem::JohnGoerzenUrl {..} => 'h',
+            &MenuItem::Search {..} => '7',
+            &MenuItem::Binary {..} => '9',
+            &MenuItem::Gif {..} => 'g',
+            &MenuItem::Image {..} => 'I',
+            &MenuItem::Archive {..} => '5',
+            &MenuItem::Sound {..} => 's',
+            &MenuItem::Elsewhere {item_type, ..} => item_type,
+        }
+    }
+
+    // Where the item leads on this hole, if anywhere
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            &MenuItem::Text {ref path, ..} |
+            &MenuItem::Directory {ref path, ..} |
+            &MenuItem::Search {ref path, ..} |
+            &MenuItem::Binary {ref path, ..} |
+            &MenuItem::Gif {ref path, ..} |
+            &MenuItem::Image {ref path, ..} |
+            &MenuItem::Archive {ref path, ..} |
+            &MenuItem::Sound {ref path, ..} => Some(path),
+            &MenuItem::Info {..} | &MenuItem::JohnGoerzenUrl {..} | &MenuItem::Elsewhere {..} => None,
+        }
+    }
+
+    pub fn desc(&self) -> &str {
+        match self {
+            &MenuItem::Text {ref desc, ..} |
+            &MenuItem::Directory {ref desc, ..} |
+            &MenuItem::Info {ref desc} |
+            &MenuItem::JohnGoerzenUrl {ref desc, ..} |
+            &MenuItem::Search {ref desc, ..} |
+            &MenuItem::Binary {ref desc, ..} |
+            &MenuItem::Gif {ref desc, ..} |
+            &MenuItem::Image {ref desc, ..} |
+            &MenuItem::Archive {ref desc, ..} |
+            &MenuItem::Sound {ref desc, ..} |
+            &MenuItem::Elsewhere {ref desc, ..} => desc,
+        }
+    }
+}
+
+// Extensions we know how to serve as something other than text, with their item type and MIME type
+static BINARY_EXTENSIONS: &'static [(&'static str, char, &'static str)] = &[
+    ("bin", '9', "application/octet-stream"),
+    ("img", '9', "application/octet-stream"),
+    ("pdf", '9', "application/pdf"),
+    ("gif", 'g', "image/gif"),
+    ("png", 'I', "image/png"),
+    ("jpg", 'I', "image/jpeg"),
+    ("jpeg", 'I', "image/jpeg"),
+    ("bmp", 'I', "image/bmp"),
+    ("zip", '5', "application/zip"),
+    ("gz", '5', "application/gzip"),
+    ("tgz", '5', "application/gzip"),
+    ("tar", '5', "application/x-tar"),
+    ("wav", 's', "audio/wav"),
+    ("mp3", 's', "audio/mpeg"),
+    ("ogg", 's', "audio/ogg"),
+];
+
+fn binary_extension(name: &str) -> Option<&'static (&'static str, char, &'static str)> {
+    let file = name.rsplit('/').next().unwrap_or(name);
+    let ext = match file.rfind('.') {
+        Some(dot) => file[dot + 1..].to_lowercase(),
+        None => return None,
+    };
+    BINARY_EXTENSIONS.iter().find(|&&(known, _, _)| known == ext)
+}
+
+// The binary item type for a file, going by its extension
+pub fn binary_type(name: &str) -> Option<char> {
+    binary_extension(name).map(|&(_, item_type, _)| item_type)
+}
+
+// What to tell clients that want to know what they're getting
+pub fn binary_mime(item_type: char, name: &str) -> &'static str {
+    match (binary_extension(name), item_type) {
+        (Some(&(_, _, mime)), _) => mime,
+        (None, 'g') => "image/gif",
+        (None, _) => "application/octet-stream",
+    }
+}
+
+
 // Internals
 
 #[derive(Clone,Debug)]
@@ -91,13 +263,6 @@ enum State {
     Newline,
 }
 
-#[derive(Debug)]
-enum Token {
-    Extra(u8),
-    Path(u8),
-    Newline,
-}
-
 #[derive(Clone,Debug)]
 pub struct ExternalAddr {
     host: String,
@@ -162,114 +327,322 @@ impl ::std::str::FromStr for ExternalAddr {
     }
 }
 
-// Through careful research, this number has been chosen to anger as many people as possible
-const READ_BUFFER_SIZE: usize = 1;
+// Who to blame, as far as Gopher+ clients are concerned
+static ADMIN: &'static str = "FROG SYSTEMS <root@frog.tips>";
+
+// Plenty for any selector a reasonable gopher would send
+const READ_BUFFER_SIZE: usize = 1024;
 
 const CR: u8 = '\r' as u8;
 const LF: u8 = '\n' as u8;
+const TAB: u8 = '\t' as u8;
+const SPACE: u8 = ' ' as u8;
 
 #[derive(Clone,Debug)]
-pub struct Protocol<'a> {
-    ext_addr: &'a ExternalAddr,
+pub struct Protocol {
+    ext_addr: ExternalAddr,
     state: State,
-    remaining: Vec<u8>,
     max_line_len: usize,
+    // Remembered from the last read so the response can be written in kind
+    selector: String,
+    plus: Option<PlusRequest>,
+    // What the selector is called in the menu that leads to it, for Gopher+ attributes
+    desc: Option<String>,
 }
 
-impl<'a> Protocol<'a> {
+impl Protocol {
     pub fn new(ext_addr: &ExternalAddr, max_line_len: usize) -> Protocol {
         Protocol {
-            ext_addr: ext_addr,
+            ext_addr: ext_addr.clone(),
             state: State::Idle,
-            remaining: Vec::with_capacity(READ_BUFFER_SIZE),
             max_line_len: max_line_len,
+            selector: String::new(),
+            plus: None,
+            desc: None,
         }
     }
 
-    fn read_stream(&mut self, stream: &mut TcpStream) -> Result<Option<Token>, ProtocolError> {
-        loop {
-            while let Some(byte) = self.remaining.pop() {
-                let (new_state, token) = match (&self.state, byte) {
-                    (&State::Idle, CR) | (&State::Path, CR) | (&State::Extra, CR) => (State::Newline, None),
-                    (&State::Idle, w) | (&State::Path, w) if w == 9 || w == 32 => (State::Extra, None),
-                    (&State::Idle, b) | (&State::Path, b) => (State::Path, Some(Token::Path(b))),
-                    (&State::Extra, b) => (State::Extra, Some(Token::Extra(b))),
-                    (&State::Newline, LF) => (State::Idle, Some(Token::Newline)),
-                    (&State::Newline, _) => (State::Idle, None),
-                };
+    // Feeds as much of the buffer as it can to the builder. Returns how many bytes were
+    // used and whether they finished the line.
+    fn scan(&mut self, buf: &[u8], selector_builder: &mut SelectorBuilder) -> Result<(usize, bool), ProtocolError> {
+        let mut used = 0;
 
-                self.state = new_state;
+        while used < buf.len() {
+            let rest = &buf[used..];
+            match self.state {
+                State::Idle | State::Path => {
+                    let run = rest.iter()
+                                  .position(|&b| b == CR || b == TAB || b == SPACE)
+                                  .unwrap_or(rest.len());
+                    if run > 0 {
+                        try!(selector_builder.extend_path(&rest[..run]));
+                        self.state = State::Path;
+                    }
+                    if run < rest.len() {
+                        self.state = if rest[run] == CR { State::Newline } else { State::Extra };
+                        used += 1;
+                    }
+                    used += run;
+                },
+                State::Extra => {
+                    let run = rest.iter()
+                                  .position(|&b| b == CR)
+                                  .unwrap_or(rest.len());
+                    if run > 0 {
+                        try!(selector_builder.extend_extra(&rest[..run]));
+                    }
+                    if run < rest.len() {
+                        self.state = State::Newline;
+                        used += 1;
+                    }
+                    used += run;
+                },
+                State::Newline => {
+                    // Whatever follows a lone CR is dropped on the floor
+                    self.state = State::Idle;
+                    used += 1;
+                    if rest[0] == LF {
+                        return Ok((used, true));
+                    }
+                },
+            }
+        }
 
-                if token.is_some() {
-                    return Ok(token);
-                }
+        Ok((used, false))
+    }
+
+    // Reads up to the end of the selector line, however long the stream takes to get there
+    pub fn read<R: AsyncRead>(self, reader: R) -> ReadSelector<R> {
+        let selector_builder = SelectorBuilder::new(self.max_line_len);
+        ReadSelector {
+            reader: Some(reader),
+            protocol: Some(self),
+            selector_builder: selector_builder,
+            buf: vec![0; READ_BUFFER_SIZE],
+        }
+    }
+
+    fn finish(&mut self, selector_builder: &mut SelectorBuilder) -> Result<Selector, ProtocolError> {
+        let (selector, plus) = try!(selector_builder.build());
+        self.selector = match selector {
+            Selector::Path(ref path) => path.val().clone(),
+            Selector::Empty => String::new(),
+        };
+        self.plus = plus;
+        Ok(selector)
+    }
+
+    // Whether the gopher asked about the item rather than for it
+    pub fn wants_attributes(&self) -> bool {
+        match self.plus {
+            Some(PlusRequest::Attributes) | Some(PlusRequest::DirectoryAttributes) => true,
+            Some(PlusRequest::Item(_)) | None => false,
+        }
+    }
+
+    pub fn described_as<S: Into<String>>(&mut self, desc: S) {
+        self.desc = Some(desc.into());
+    }
+
+    // The selector stands in for menus that never said what they call it
+    fn desc(&self) -> String {
+        self.desc.clone().unwrap_or(self.selector.clone())
+    }
+
+    fn write_item<W: Write>(&self, stream: &mut W, item: &MenuItem) -> Result<(), ProtocolError> {
+        let addr = &self.ext_addr;
+        // Tell clients that there is more to learn about everything except info lines
+        let plus = "\t+";
+        match item {
+            &MenuItem::Text {ref path, ref desc} => {
+                try!(write!(stream, "0{}\t{}\t{}\t{}{}\r\n", desc, path.val(), addr.host, addr.port, plus))
+            }
+            &MenuItem::Directory {ref path, ref desc} => {
+                try!(write!(stream, "1{}\t{}\t{}\t{}{}\r\n", desc, path.val(), addr.host, addr.port, plus))
             }
+            &MenuItem::JohnGoerzenUrl {ref url, ref desc} => {
+                try!(write!(stream, "h{}\tURL:{}\t{}\t{}{}\r\n", desc, url, addr.host, addr.port, plus))
+            },
+            &MenuItem::Info {ref desc} => {
+                for line in desc.split("\n") {
+                    try!(write!(stream, "i{}\t\t\t\r\n", line))
+                }
+            },
+            &MenuItem::Search {ref path, ref desc} => {
+                try!(write!(stream, "7{}\t{}\t{}\t{}{}\r\n", desc, path, addr.host, addr.port, plus))
+            },
+            &MenuItem::Binary {ref path, ref desc} |
+            &MenuItem::Gif {ref path, ref desc} |
+            &MenuItem::Image {ref path, ref desc} |
+            &MenuItem::Archive {ref path, ref desc} |
+            &MenuItem::Sound {ref path, ref desc} => {
+                try!(write!(stream, "{}{}\t{}\t{}\t{}{}\r\n", item.item_type(), desc, path.val(), addr.host, addr.port, plus))
+            },
+            // Whether the other server speaks Gopher+ is its own business
+            &MenuItem::Elsewhere {item_type, ref path, ref desc, ref host, port} => {
+                try!(write!(stream, "{}{}\t{}\t{}\t{}\r\n", item_type, desc, path.val(), host, port))
+            },
+        }
 
-            let mut buffer = [0; READ_BUFFER_SIZE];
-            let bytes_read = try!(stream.read(&mut buffer));
+        Ok(())
+    }
 
-            // Just stop trying already
-            if bytes_read == 0 {
-                return Ok(None);
+    // Every line ends in CRLF, and a line that starts with a period gets another so it can't end things early
+    fn write_text<W: Write>(&self, stream: &mut W, text: &str) -> Result<(), ProtocolError> {
+        for line in text.lines() {
+            if line.starts_with('.') {
+                try!(write!(stream, "."));
             }
+            try!(write!(stream, "{}\r\n", line));
+        }
 
-            self.remaining.extend_from_slice(&buffer[0 .. bytes_read]);
-            self.remaining.reverse();
+        Ok(())
+    }
+
+    fn write_menu<W: Write>(&mut self, stream: &mut W, menu: &Menu) -> Result<(), ProtocolError> {
+        for item in menu.items().iter() {
+            try!(self.write_item(stream, item));
         }
+
+        Ok(())
     }
 
-    pub fn read(&mut self, stream: &mut TcpStream) -> Result<Selector, ProtocolError> {
-        let mut selector_builder = SelectorBuilder::new(self.max_line_len);
+    fn write_admin<W: Write>(&self, stream: &mut W) -> Result<(), ProtocolError> {
+        // No Mod-Date, since frog cannot say when most of what it serves last changed
+        try!(write!(stream, "+ADMIN:\r\n Admin: {}\r\n", ADMIN));
+        Ok(())
+    }
 
-        while let Some(token) = try!(self.read_stream(stream)) {
-            match token {
-                Token::Path(byte) => {
-                    try!(selector_builder.push_path(byte));
-                },
-                Token::Extra(byte) => {
-                    try!(selector_builder.push_extra(byte));
-                },
-                Token::Newline => {
-                    return selector_builder.build();
-                },
-            };
+    fn write_attributes<W: Write>(&self, stream: &mut W, item: &MenuItem, size: Option<usize>) -> Result<(), ProtocolError> {
+        match item {
+            &MenuItem::Info {ref desc} => {
+                // Info lines have nothing to administer or view
+                for line in desc.split("\n") {
+                    try!(write!(stream, "+INFO: i{}\t\t\t\r\n", line))
+                }
+                return Ok(());
+            },
+            _ => {
+                try!(write!(stream, "+INFO: "));
+                try!(self.write_item(stream, item));
+            },
+        }
+
+        try!(self.write_admin(stream));
+
+        let mime = match item {
+            &MenuItem::Directory {..} => "application/gopher+-menu",
+            &MenuItem::JohnGoerzenUrl {..} => "text/html",
+            &MenuItem::Binary {ref path, ..} |
+            &MenuItem::Gif {ref path, ..} |
+            &MenuItem::Image {ref path, ..} |
+            &MenuItem::Archive {ref path, ..} |
+            &MenuItem::Sound {ref path, ..} => binary_mime(item.item_type(), path.val()),
+            _ => "text/plain",
+        };
+
+        try!(write!(stream, "+VIEWS:\r\n {}:", mime));
+        if let Some(size) = size {
+            // Sizes are in kilobytes, rounded up
+            try!(write!(stream, " <{}k>", (size + 1023) / 1024));
         }
+        try!(write!(stream, "\r\n"));
 
-        Err(ProtocolError::UnfinishedBusiness)
+        Ok(())
     }
 
-    fn write_menu(&mut self, stream: &mut TcpStream, menu: &Menu) -> Result<(), ProtocolError> {
-        let addr = &self.ext_addr;
+    fn write_menu_attributes<W: Write>(&mut self, stream: &mut W, menu: &Menu) -> Result<(), ProtocolError> {
         for item in menu.items().iter() {
-            match item {
-                &MenuItem::Text {ref path, ref desc} => {
-                    try!(write!(stream, "0{}\t{}\t{}\t{}\r\n", desc, path.val(), addr.host, addr.port))
-                }
-                &MenuItem::JohnGoerzenUrl {ref url, ref desc} => {
-                    try!(write!(stream, "h{}\tURL:{}\t{}\t{}\r\n", desc, url, addr.host, addr.port))
-                },
-                &MenuItem::Info {ref desc} => {
-                    for line in desc.split("\n") {
-                        try!(write!(stream, "i{}\t\t\t\r\n", line))
-                    }
-                },
-                &MenuItem::Search {ref path, ref desc} => {
-                    try!(write!(stream, "7{}\t{}\t{}\t{}\r\n", desc, path, addr.host, addr.port))
-                },
-            }
+            try!(self.write_attributes(stream, item, menu.size(item)));
         }
 
         Ok(())
     }
 
-    pub fn write(&mut self, stream: &mut TcpStream, selected: &Selected) -> Result<(), ProtocolError> {
+    fn write_plus<W: Write>(&mut self, stream: &mut W, selected: &Selected, plus: &PlusRequest) -> Result<(), ProtocolError> {
+        let menu: Option<&Menu> = match selected {
+            &Selected::ForeverMenu(ref menu) => Some(*menu),
+            &Selected::TempMenu(ref menu) => Some(&**menu),
+            _ => None,
+        };
+
+        match (plus, selected, menu) {
+            (_, &Selected::Error(ref why), _) => {
+                // Error code 1 is "item is not available"
+                try!(write!(stream, "--1\r\n1 {}\r\n{}\r\n", ADMIN, why))
+            },
+            (_, &Selected::Failed(ref why), _) => {
+                // Error code 2 is "try again later"
+                try!(write!(stream, "--1\r\n2 {}\r\n{}\r\n", ADMIN, why))
+            },
+            (_, &Selected::SlowDown, _) => {
+                try!(write!(stream, "--1\r\n2 {}\r\n{}\r\n", ADMIN, SLOW_DOWN))
+            },
+            (&PlusRequest::Item(_), &Selected::Text(ref text), _) => {
+                // Known length, so no terminator and no lone periods to escape, but lines still end in CRLF
+                let text: String = text.lines().map(|line| format!("{}\r\n", line)).collect();
+                try!(write!(stream, "+{}\r\n{}", text.len(), text));
+                return Ok(());
+            },
+            (&PlusRequest::Item(_), &Selected::Binary(ref bytes), _) => {
+                try!(write!(stream, "+{}\r\n", bytes.len()));
+                try!(stream.write_all(bytes));
+                return Ok(());
+            },
+            (&PlusRequest::Item(_), _, Some(menu)) => {
+                try!(write!(stream, "+-1\r\n"));
+                try!(self.write_menu(stream, menu));
+            },
+            (&PlusRequest::DirectoryAttributes, _, Some(menu)) => {
+                try!(write!(stream, "+-1\r\n"));
+                try!(self.write_menu_attributes(stream, menu));
+            },
+            (_, &Selected::Text(ref text), _) => {
+                let item = MenuItem::Text {
+                    path: Path::from(self.selector.clone()),
+                    desc: self.desc(),
+                };
+                try!(write!(stream, "+-1\r\n"));
+                try!(self.write_attributes(stream, &item, Some(text.len())));
+            },
+            (_, &Selected::Binary(ref bytes), _) => {
+                let item_type = binary_type(&self.selector).unwrap_or('9');
+                let item = MenuItem::binary(item_type, Path::from(self.selector.clone()), self.desc()).unwrap();
+                try!(write!(stream, "+-1\r\n"));
+                try!(self.write_attributes(stream, &item, Some(bytes.len())));
+            },
+            (_, _, _) => {
+                // The attributes of a menu itself
+                try!(write!(stream, "+-1\r\n+INFO: 1{}\t{}\t{}\t{}\t+\r\n",
+                            self.desc(), self.selector, self.ext_addr.host, self.ext_addr.port));
+                try!(self.write_admin(stream));
+                try!(write!(stream, "+VIEWS:\r\n application/gopher+-menu:\r\n"));
+            },
+        };
+
+        Ok(try!(write!(stream, ".\r\n")))
+    }
+
+    pub fn write<W: Write>(&mut self, stream: &mut W, selected: &Selected) -> Result<(), ProtocolError> {
+        if let Some(plus) = self.plus.clone() {
+            return self.write_plus(stream, selected, &plus);
+        }
+
         match selected {
             &Selected::Text(ref text) => {
-                try!(write!(stream, "{}\r\n", text))
+                try!(self.write_text(stream, text))
+            },
+            &Selected::Binary(ref bytes) => {
+                // The client knows it's done when we hang up
+                try!(stream.write_all(bytes));
+                return Ok(());
             },
-            &Selected::Error(ref why) => {
+            &Selected::Error(ref why) | &Selected::Failed(ref why) => {
                 try!(write!(stream, "3{}\r\n", why))
             },
+            &Selected::SlowDown => {
+                try!(write!(stream, "3{}\r\n", SLOW_DOWN))
+            },
             &Selected::ForeverMenu(ref menu) => {
                 try!(self.write_menu(stream, *menu))
             },
@@ -284,6 +657,44 @@ impl<'a> Protocol<'a> {
 }
 
 
+// Resolves to the stream, the protocol that read from it and what the gopher asked for
+pub struct ReadSelector<R> {
+    reader: Option<R>,
+    protocol: Option<Protocol>,
+    selector_builder: SelectorBuilder,
+    buf: Vec<u8>,
+}
+
+impl<R: AsyncRead> Future for ReadSelector<R> {
+    type Item = (R, Protocol, Selector);
+    type Error = ProtocolError;
+
+    fn poll(&mut self) -> Poll<Self::Item, ProtocolError> {
+        loop {
+            let done = {
+                let reader = self.reader.as_mut().expect("polled ReadSelector after it finished");
+                let protocol = self.protocol.as_mut().expect("polled ReadSelector after it finished");
+
+                let len = try_ready!(reader.poll_read(&mut self.buf));
+
+                // Just stop trying already
+                if len == 0 {
+                    return Err(ProtocolError::UnfinishedBusiness);
+                }
+
+                try!(protocol.scan(&self.buf[..len], &mut self.selector_builder)).1
+            };
+
+            if done {
+                let mut protocol = self.protocol.take().unwrap();
+                let selector = try!(protocol.finish(&mut self.selector_builder));
+                return Ok(Async::Ready((self.reader.take().unwrap(), protocol, selector)));
+            }
+        }
+    }
+}
+
+
 #[derive(Debug)]
 pub enum ProtocolError {
     LineTooBigError,
@@ -292,6 +703,18 @@ pub enum ProtocolError {
     IoError(io::Error),
 }
 
+impl ProtocolError {
+    // Short enough for a metric label
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ProtocolError::LineTooBigError => "line_too_big",
+            ProtocolError::ParseLineError(_) => "parse_line",
+            ProtocolError::UnfinishedBusiness => "unfinished_business",
+            ProtocolError::IoError(_) => "io",
+        }
+    }
+}
+
 impl fmt::Display for ProtocolError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -350,9 +773,9 @@ impl SelectorBuilder {
         }
     }
 
-    fn check_capacity(&self) -> Result<(), ProtocolError> {
-        if self.max_line_len == self.path_buffer.len() ||
-           self.max_line_len == self.extra_buffer.len() {
+    fn check_capacity(&self, path_len: usize, extra_len: usize) -> Result<(), ProtocolError> {
+        if self.max_line_len < path_len ||
+           self.max_line_len < extra_len {
                Err(ProtocolError::LineTooBigError)
            } else {
                Ok(())
@@ -365,37 +788,312 @@ impl SelectorBuilder {
         self.extra_buffer.clear();
     }
 
-    fn push_path(&mut self, byte: u8) -> Result<(), ProtocolError> {
-        try!(self.check_capacity());
-        self.path_buffer.push(byte);
+    fn extend_path(&mut self, bytes: &[u8]) -> Result<(), ProtocolError> {
+        let (path_len, extra_len) = (self.path_buffer.len() + bytes.len(), self.extra_buffer.len());
+        try!(self.check_capacity(path_len, extra_len));
+        self.path_buffer.extend_from_slice(bytes);
         Ok(())
     }
 
-    fn push_extra(&mut self, byte: u8) -> Result<(), ProtocolError> {
-        try!(self.check_capacity());
-        self.extra_buffer.push(byte);
+    fn extend_extra(&mut self, bytes: &[u8]) -> Result<(), ProtocolError> {
+        let (path_len, extra_len) = (self.path_buffer.len(), self.extra_buffer.len() + bytes.len());
+        try!(self.check_capacity(path_len, extra_len));
+        self.extra_buffer.extend_from_slice(bytes);
         Ok(())
     }
 
-    fn build(&mut self) -> Result<Selector, ProtocolError> {
-        if self.path_buffer.len() == 0 {
-            self.reset();
-            return Ok(Selector::Empty);
-        }
-
-
+    fn build(&mut self) -> Result<(Selector, Option<PlusRequest>), ProtocolError> {
         let path_bytes = self.path_buffer.clone();
         let extra_bytes = self.extra_buffer.clone();
 
         self.reset();
 
         let path = try!(String::from_utf8(path_bytes));
-        let extra = if extra_bytes.len() == 0 {
+        let extra = try!(String::from_utf8(extra_bytes));
+
+        // Gopher+ puts its business in the last tab-delimited field, after any search terms
+        let (extra, plus) = match extra.rfind('\t') {
+            Some(i) => match PlusRequest::parse(&extra[i + 1..]) {
+                Some(plus) => (extra[..i].to_string(), Some(plus)),
+                None => (extra, None),
+            },
+            None => match PlusRequest::parse_alone(&extra) {
+                Some(plus) => (String::new(), Some(plus)),
+                None => (extra, None),
+            },
+        };
+
+        if path.len() == 0 {
+            return Ok((Selector::Empty, plus));
+        }
+
+        let extra = if extra.len() == 0 {
             None
         } else {
-            Some(try!(String::from_utf8(extra_bytes)))
+            Some(extra)
         };
 
-        Ok(Selector::Path(Path::new(path, extra)))
+        Ok((Selector::Path(Path::new(path, extra)), plus))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self,Cursor,Read};
+
+    use futures::Future;
+    use tokio::io::AsyncRead;
+
+    use super::{ExternalAddr,Menu,MenuItem,Protocol,ProtocolError,Selected,Selector,PlusRequest,Path};
+    use super::{binary_type,binary_mime};
+
+
+    // Never has more than a byte to give at a time
+    struct Trickle(Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = if buf.is_empty() { 0 } else { 1 };
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    impl AsyncRead for Trickle {}
+
+    fn read_with<R: AsyncRead>(reader: R) -> Result<(Selector, Option<PlusRequest>), ProtocolError> {
+        let ext_addr = ExternalAddr::new("127.0.0.1", 70);
+        let (_, protocol, selector) = try!(Protocol::new(&ext_addr, 16).read(reader).wait());
+        Ok((selector, protocol.plus.clone()))
+    }
+
+    // Reads the line a byte at a time and all at once, which had better agree
+    fn read(line: &str) -> Result<(Selector, Option<PlusRequest>), ProtocolError> {
+        let bulk = read_with(Cursor::new(line.as_bytes().to_vec()));
+        let trickle = read_with(Trickle(Cursor::new(line.as_bytes().to_vec())));
+        assert_eq!(format!("{:?}", bulk), format!("{:?}", trickle));
+        bulk
+    }
+
+    fn path(selector: Selector) -> Path {
+        match selector {
+            Selector::Path(path) => path,
+            Selector::Empty => panic!("expected a path"),
+        }
+    }
+
+    #[test]
+    fn reads_selectors() {
+        assert_eq!(path(read("/README\r\n").unwrap().0), Path::from("/README"));
+        assert!(match read("\r\n").unwrap().0 { Selector::Empty => true, _ => false });
+
+        // Tabs and spaces both start the extra, which keeps any that follow
+        assert_eq!(path(read("/TIP/SEARCH\tFROG TOY\r\n").unwrap().0), Path::new("/TIP/SEARCH", Some("FROG TOY")));
+        assert_eq!(path(read("/TIP/SEARCH FROG\r\n").unwrap().0), Path::new("/TIP/SEARCH", Some("FROG")));
+    }
+
+    #[test]
+    fn only_crlf_ends_a_line() {
+        // A bare LF is part of the selector, and whatever follows a lone CR is dropped
+        assert_eq!(path(read("/A\nB\r\n").unwrap().0), Path::from("/A\nB"));
+        assert_eq!(path(read("/A\rXB\r\n").unwrap().0), Path::from("/AB"));
+
+        // Anything after the line is left alone
+        assert_eq!(path(read("/A\r\n/B\r\n").unwrap().0), Path::from("/A"));
+    }
+
+    #[test]
+    fn long_lines_are_refused() {
+        assert!(read("/0123456789ABCDE\r\n").is_ok());
+        assert!(match read("/0123456789ABCDEF\r\n") { Err(ProtocolError::LineTooBigError) => true, _ => false });
+        assert!(match read("/\t0123456789ABCDEFG\r\n") { Err(ProtocolError::LineTooBigError) => true, _ => false });
+    }
+
+    #[test]
+    fn unfinished_lines_are_refused() {
+        assert!(match read("/README") { Err(ProtocolError::UnfinishedBusiness) => true, _ => false });
+        assert!(match read("/README\r") { Err(ProtocolError::UnfinishedBusiness) => true, _ => false });
+    }
+
+    #[test]
+    fn plain_gopher_has_no_plus() {
+        let (selector, plus) = read("/TIP/SEARCH\tFROG\r\n").unwrap();
+        assert_eq!(path(selector), Path::new("/TIP/SEARCH", Some("FROG")));
+        assert_eq!(plus, None);
+    }
+
+    #[test]
+    fn gopher_plus_item() {
+        let (selector, plus) = read("/README\t+\r\n").unwrap();
+        assert_eq!(path(selector), Path::from("/README"));
+        assert_eq!(plus, Some(PlusRequest::Item(None)));
+
+        let (_, plus) = read("/README\t+text/plain\r\n").unwrap();
+        assert_eq!(plus, Some(PlusRequest::Item(Some("text/plain".to_string()))));
+    }
+
+    #[test]
+    fn gopher_plus_attributes() {
+        let (selector, plus) = read("/README\t!\r\n").unwrap();
+        assert_eq!(path(selector), Path::from("/README"));
+        assert_eq!(plus, Some(PlusRequest::Attributes));
+
+        let (selector, plus) = read("\t$\r\n").unwrap();
+        assert!(match selector { Selector::Empty => true, _ => false });
+        assert_eq!(plus, Some(PlusRequest::DirectoryAttributes));
+    }
+
+    #[test]
+    fn searches_that_look_like_gopher_plus_are_searches() {
+        for terms in &["+FROG", "$5", "!FROG"] {
+            let (selector, plus) = read(&format!("/TIP/SEARCH\t{}\r\n", terms)).unwrap();
+            assert_eq!(path(selector), Path::new("/TIP/SEARCH", Some(*terms)));
+            assert_eq!(plus, None);
+        }
+
+        // As its own field it is Gopher+ again
+        let (selector, plus) = read("/TIP/SEARCH\t+FROG\t+\r\n").unwrap();
+        assert_eq!(path(selector), Path::new("/TIP/SEARCH", Some("+FROG")));
+        assert_eq!(plus, Some(PlusRequest::Item(None)));
+    }
+
+    #[test]
+    fn gopher_plus_search_keeps_terms() {
+        let (selector, plus) = read("/TIP/SEARCH\tFROG\t+\r\n").unwrap();
+        assert_eq!(path(selector), Path::new("/TIP/SEARCH", Some("FROG")));
+        assert_eq!(plus, Some(PlusRequest::Item(None)));
+    }
+
+    fn write(text: &str) -> String {
+        let mut protocol = Protocol::new(&ExternalAddr::new("127.0.0.1", 70), 16);
+        let mut resp = Vec::new();
+        protocol.write(&mut resp, &Selected::Text(Box::new(text.to_string()))).unwrap();
+        String::from_utf8(resp).unwrap()
+    }
+
+    #[test]
+    fn text_is_dot_stuffed() {
+        assert_eq!(write("FROG.\n.\n...IS NOT A TOY.\n"), "FROG.\r\n..\r\n....IS NOT A TOY.\r\n.\r\n");
+        assert_eq!(write(".FROG\r\nTOAD\nFROG"), "..FROG\r\nTOAD\r\nFROG\r\n.\r\n");
+        assert_eq!(write(""), ".\r\n");
+    }
+
+    #[test]
+    fn bundled_texts_survive_the_trip() {
+        let texts = [
+            include_str!("../txt/README"),
+            include_str!("../txt/FROG_MODELS"),
+            include_str!("../txt/FIRMWARE_V2"),
+            include_str!("../txt/JOB_OPENINGS"),
+            include_str!("../txt/EVACUATION_PROCEDURE"),
+            include_str!("../txt/USER_MANUAL/FOREWORD"),
+            include_str!("../txt/USER_MANUAL/CHAPTER_01"),
+            include_str!("../txt/USER_MANUAL/CHAPTER_02"),
+            include_str!("../txt/USER_MANUAL/CHAPTER_03"),
+            include_str!("../txt/USER_MANUAL/CHAPTER_04"),
+            include_str!("../txt/USER_MANUAL/CHAPTER_05"),
+            include_str!("../txt/USER_MANUAL/CHAPTER_06"),
+            include_str!("../txt/USER_MANUAL/APPENDIX_A"),
+            include_str!("../txt/USER_MANUAL/APPENDIX_B"),
+            include_str!("../txt/USER_MANUAL/APPENDIX_C"),
+        ];
+
+        for text in texts.iter() {
+            let resp = write(text);
+            assert!(resp.ends_with("\r\n.\r\n"));
+            assert!(!resp.replace("\r\n", "").contains('\n'));
+
+            // Undoing what a client undoes gives back every line, and only the last is a lone period
+            let mut lines: Vec<&str> = resp.split("\r\n").collect();
+            assert_eq!(lines.pop(), Some(""));
+            assert_eq!(lines.pop(), Some("."));
+            let unstuffed: Vec<&str> = lines.iter().map(|line| {
+                assert!(*line != ".");
+                if line.starts_with('.') { &line[1..] } else { *line }
+            }).collect();
+            assert_eq!(unstuffed, text.lines().collect::<Vec<_>>());
+        }
+    }
+
+    struct PhotoMenu;
+
+    impl Menu for PhotoMenu {
+        fn items(&self) -> Vec<MenuItem> {
+            vec![MenuItem::Image {path: Path::from("/PHOTOS/FROG.PNG"), desc: "FROG, FRONT VIEW.".to_string()},
+                 MenuItem::Archive {path: Path::from("/FIRMWARE.ZIP"), desc: "FIRMWARE.".to_string()}]
+        }
+    }
+
+    #[test]
+    fn binaries_are_sent_as_they_are() {
+        let bytes = b"\x89PNG\r\n.\r\n".to_vec();
+        let mut protocol = Protocol::new(&ExternalAddr::new("127.0.0.1", 70), 16);
+        let mut resp = Vec::new();
+        protocol.write(&mut resp, &Selected::Binary(Box::new(bytes.clone()))).unwrap();
+        assert_eq!(resp, bytes);
+
+        // Gopher+ clients are told how much to expect
+        let (_, mut protocol, _) = Protocol::new(&ExternalAddr::new("127.0.0.1", 70), 16).read(Cursor::new(b"/FROG.PNG\t+\r\n".to_vec())).wait().unwrap();
+        let mut resp = Vec::new();
+        protocol.write(&mut resp, &Selected::Binary(Box::new(bytes.clone()))).unwrap();
+        assert_eq!(resp, [&b"+9\r\n"[..], &bytes[..]].concat());
+    }
+
+    struct ReadmeMenu;
+
+    impl Menu for ReadmeMenu {
+        fn items(&self) -> Vec<MenuItem> {
+            vec![MenuItem::Text {path: Path::from("/README"), desc: "README.".to_string()},
+                 MenuItem::Text {path: Path::from("/ELSEWHERE"), desc: "ELSEWHERE.".to_string()}]
+        }
+
+        fn size(&self, item: &MenuItem) -> Option<usize> {
+            match item.path() {
+                Some(path) if path.val() == "/README" => Some(1025),
+                _ => None,
+            }
+        }
+    }
+
+    fn write_plus(request: &str, desc: Option<&str>, selected: &Selected) -> String {
+        let (_, mut protocol, _) = Protocol::new(&ExternalAddr::new("127.0.0.1", 70), 16).read(Cursor::new(request.as_bytes().to_vec())).wait().unwrap();
+        if let Some(desc) = desc {
+            protocol.described_as(desc);
+        }
+        let mut resp = Vec::new();
+        protocol.write(&mut resp, selected).unwrap();
+        String::from_utf8(resp).unwrap()
+    }
+
+    #[test]
+    fn attributes_say_what_the_menu_knows() {
+        let resp = write_plus("\t$\r\n", None, &Selected::TempMenu(Box::new(ReadmeMenu)));
+        assert!(resp.contains("+INFO: 0README.\t/README\t127.0.0.1\t70\t+\r\n+ADMIN:\r\n Admin: FROG SYSTEMS <root@frog.tips>\r\n\
+                               +VIEWS:\r\n text/plain: <2k>\r\n"));
+        assert!(resp.contains("+INFO: 0ELSEWHERE.\t/ELSEWHERE\t127.0.0.1\t70\t+\r\n+ADMIN:\r\n Admin: FROG SYSTEMS <root@frog.tips>\r\n\
+                               +VIEWS:\r\n text/plain:\r\n"));
+        assert!(!resp.contains("Mod-Date"));
+
+        let resp = write_plus("/README\t!\r\n", Some("README."), &Selected::Text(Box::new("FROG.".to_string())));
+        assert!(resp.starts_with("+-1\r\n+INFO: 0README.\t/README\t127.0.0.1\t70\t+\r\n"));
+        assert!(resp.contains("+VIEWS:\r\n text/plain: <1k>\r\n"));
+
+        // Without a description the selector has to do
+        let resp = write_plus("/README\t!\r\n", None, &Selected::Text(Box::new("FROG.".to_string())));
+        assert!(resp.starts_with("+-1\r\n+INFO: 0/README\t/README\t"));
+    }
+
+    #[test]
+    fn binary_items_have_their_own_types() {
+        let mut protocol = Protocol::new(&ExternalAddr::new("127.0.0.1", 70), 16);
+        let mut resp = Vec::new();
+        protocol.write(&mut resp, &Selected::TempMenu(Box::new(PhotoMenu))).unwrap();
+        assert_eq!(String::from_utf8(resp).unwrap(),
+                   "IFROG, FRONT VIEW.\t/PHOTOS/FROG.PNG\t127.0.0.1\t70\t+\r\n5FIRMWARE.\t/FIRMWARE.ZIP\t127.0.0.1\t70\t+\r\n.\r\n");
+
+        assert_eq!(binary_type("/PHOTOS/FROG.PNG"), Some('I'));
+        assert_eq!(binary_type("/DOCS/USER_MANUAL.pdf"), Some('9'));
+        assert_eq!(binary_type("/DOCS.OLD/README"), None);
+        assert_eq!(binary_mime('I', "/PHOTOS/FROG.PNG"), "image/png");
+        assert_eq!(binary_mime('9', "/FIRMWARE"), "application/octet-stream");
     }
 }
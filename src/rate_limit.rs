@@ -0,0 +1,313 @@
+// Keeps any one gopher from hogging frog. Every address gets a bucket of tokens that refills
+// over a minute, and asking for tips, which may mean asking frog.tips, takes from a second one.
+
+use std::collections::{HashMap,VecDeque};
+use std::net::{IpAddr,Ipv6Addr};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize,Ordering};
+use std::time::Instant;
+
+use cidr::{self,Cidr};
+
+
+pub static SLOW_DOWN: &'static str = "SLOW DOWN, FRIEND.";
+
+// Past this many, whoever has been quiet longest is forgotten
+const MAX_BUCKETS: usize = 10000;
+
+// Whose buckets an address takes from. Anyone with IPv6 has a whole /64 to hop around in, so
+// that is what gets a bucket
+fn bucket_key(peer: &IpAddr) -> IpAddr {
+    match cidr::unmapped(peer) {
+        IpAddr::V6(v6) => {
+            let s = v6.segments();
+            IpAddr::V6(Ipv6Addr::new(s[0], s[1], s[2], s[3], 0, 0, 0, 0))
+        },
+        v4 => v4,
+    }
+}
+
+// How many requests a minute, all of which may come at once. Zero means no limit.
+#[derive(Clone,Copy,Debug)]
+pub struct Limit {
+    pub per_minute: u32,
+}
+
+impl Limit {
+    pub fn unlimited() -> Limit {
+        Limit {
+            per_minute: 0,
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    filled: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &Limit, now: Instant) -> TokenBucket {
+        TokenBucket {
+            tokens: limit.per_minute as f64,
+            filled: now,
+        }
+    }
+
+    fn fill(&mut self, limit: &Limit, now: Instant) {
+        let elapsed = now.duration_since(self.filled);
+        let secs = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+        let capacity = limit.per_minute as f64;
+        self.tokens = (self.tokens + secs * capacity / 60.0).min(capacity);
+        self.filled = now;
+    }
+
+    fn take(&mut self, limit: &Limit, now: Instant) -> bool {
+        if limit.per_minute == 0 {
+            return true;
+        }
+
+        self.fill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+struct Buckets {
+    requests: TokenBucket,
+    tips: TokenBucket,
+    // When these were last taken from, counted in takes
+    used: u64,
+}
+
+// Everybody's buckets, and who took from them in what order
+struct Pond {
+    buckets: HashMap<IpAddr, Buckets>,
+    // Oldest first. Only the latest entry for an address counts, the rest are swept up now and then
+    takes: VecDeque<(IpAddr, u64)>,
+    taken: u64,
+    max_buckets: usize,
+}
+
+impl Pond {
+    fn new(max_buckets: usize) -> Pond {
+        Pond {
+            buckets: HashMap::new(),
+            takes: VecDeque::new(),
+            taken: 0,
+            max_buckets: max_buckets,
+        }
+    }
+
+    fn forget_quietest(&mut self) {
+        while let Some((peer, used)) = self.takes.pop_front() {
+            if self.buckets.get(&peer).map_or(false, |b| b.used == used) {
+                self.buckets.remove(&peer);
+                return;
+            }
+        }
+    }
+
+    fn buckets(&mut self, peer: IpAddr, requests: &Limit, tips: &Limit, now: Instant) -> &mut Buckets {
+        if !self.buckets.contains_key(&peer) {
+            while self.buckets.len() >= self.max_buckets {
+                self.forget_quietest();
+            }
+        }
+
+        self.taken += 1;
+        let taken = self.taken;
+        self.takes.push_back((peer, taken));
+        // Sweeping only once the stale entries outnumber the live ones keeps it cheap on average
+        if self.takes.len() > 2 * self.max_buckets {
+            let buckets = &self.buckets;
+            let live: VecDeque<(IpAddr, u64)> = self.takes.drain(..)
+                                                          .filter(|&(peer, used)| buckets.get(&peer).map_or(false, |b| b.used == used))
+                                                          .collect();
+            self.takes = live;
+        }
+
+        let peer_buckets = self.buckets.entry(peer).or_insert_with(|| Buckets {
+            requests: TokenBucket::new(requests, now),
+            tips: TokenBucket::new(tips, now),
+            used: 0,
+        });
+        peer_buckets.used = taken;
+        peer_buckets
+    }
+}
+
+#[derive(Clone,Copy,Debug,Eq,PartialEq)]
+pub enum Admission {
+    Welcome,
+    SlowDown,
+    // On the deny list, so not even worth an answer
+    Refused,
+}
+
+pub struct RateLimiter {
+    requests: Limit,
+    tips: Limit,
+    // Never limited
+    allow: Vec<Cidr>,
+    // Never answered
+    deny: Vec<Cidr>,
+    pond: Mutex<Pond>,
+    welcomed: AtomicUsize,
+    slowed: AtomicUsize,
+    refused: AtomicUsize,
+}
+
+impl RateLimiter {
+    pub fn new(requests: Limit, tips: Limit, allow: Vec<Cidr>, deny: Vec<Cidr>) -> RateLimiter {
+        RateLimiter {
+            requests: requests,
+            tips: tips,
+            allow: allow,
+            deny: deny,
+            pond: Mutex::new(Pond::new(MAX_BUCKETS)),
+            welcomed: AtomicUsize::new(0),
+            slowed: AtomicUsize::new(0),
+            refused: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn unlimited() -> RateLimiter {
+        RateLimiter::new(Limit::unlimited(), Limit::unlimited(), vec![], vec![])
+    }
+
+    // Takes from one of the address's buckets, making them first if need be
+    fn take<F: Fn(&mut Buckets) -> &mut TokenBucket>(&self, peer: &IpAddr, limit: &Limit, which: F) -> bool {
+        let now = Instant::now();
+        let mut pond = self.pond.lock().unwrap();
+        which(pond.buckets(bucket_key(peer), &self.requests, &self.tips, now)).take(limit, now)
+    }
+
+    // Whether a new connection from the address gets answered
+    pub fn admit(&self, peer: &IpAddr) -> Admission {
+        if self.deny.iter().any(|cidr| cidr.contains(peer)) {
+            self.refused.fetch_add(1, Ordering::SeqCst);
+            warn!("FROG REFUSED {}, WHICH IS ON THE DENY LIST ({}).", peer, self.counts());
+            return Admission::Refused;
+        }
+
+        if self.allow.iter().any(|cidr| cidr.contains(peer)) || self.take(peer, &self.requests, |b| &mut b.requests) {
+            self.welcomed.fetch_add(1, Ordering::SeqCst);
+            return Admission::Welcome;
+        }
+
+        self.slowed.fetch_add(1, Ordering::SeqCst);
+        warn!("FROG ASKED {} TO SLOW DOWN ({}).", peer, self.counts());
+        Admission::SlowDown
+    }
+
+    // Whether the address may ask for something that could mean asking frog.tips
+    pub fn admit_tip(&self, peer: &IpAddr) -> bool {
+        if self.allow.iter().any(|cidr| cidr.contains(peer)) || self.take(peer, &self.tips, |b| &mut b.tips) {
+            return true;
+        }
+
+        self.slowed.fetch_add(1, Ordering::SeqCst);
+        warn!("FROG ASKED {} TO SLOW DOWN ON TIPS ({}).", peer, self.counts());
+        false
+    }
+
+    // For the logs
+    pub fn counts(&self) -> String {
+        format!("{} WELCOMED, {} SLOWED DOWN, {} REFUSED",
+                self.welcomed.load(Ordering::SeqCst),
+                self.slowed.load(Ordering::SeqCst),
+                self.refused.load(Ordering::SeqCst))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::{Admission,Limit,RateLimiter};
+
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn buckets_run_dry() {
+        let limiter = RateLimiter::new(Limit { per_minute: 3 }, Limit { per_minute: 1 }, vec![], vec![]);
+        let frog = ip("192.0.2.1");
+        for _ in 0..3 {
+            assert_eq!(limiter.admit(&frog), Admission::Welcome);
+        }
+        assert_eq!(limiter.admit(&frog), Admission::SlowDown);
+
+        // Everyone has buckets of their own
+        assert_eq!(limiter.admit(&ip("192.0.2.2")), Admission::Welcome);
+
+        // And tips come out of a different one
+        assert!(limiter.admit_tip(&frog));
+        assert!(!limiter.admit_tip(&frog));
+        assert_eq!(limiter.counts(), "4 WELCOMED, 2 SLOWED DOWN, 0 REFUSED");
+    }
+
+    #[test]
+    fn lists_win_over_buckets() {
+        let limiter = RateLimiter::new(Limit { per_minute: 1 }, Limit { per_minute: 1 }, vec!["10.0.0.0/8".parse().unwrap()],
+                                       vec!["192.0.2.0/24".parse().unwrap()]);
+        for _ in 0..5 {
+            assert_eq!(limiter.admit(&ip("10.1.2.3")), Admission::Welcome);
+            assert!(limiter.admit_tip(&ip("10.1.2.3")));
+        }
+        assert_eq!(limiter.admit(&ip("192.0.2.1")), Admission::Refused);
+
+        // Dual-stack listeners see IPv4 gophers as IPv6
+        assert!(limiter.admit_tip(&ip("::ffff:10.1.2.3")));
+        assert_eq!(limiter.admit(&ip("::ffff:192.0.2.1")), Admission::Refused);
+    }
+
+    #[test]
+    fn no_limit_means_no_limit() {
+        let limiter = RateLimiter::unlimited();
+        for _ in 0..100 {
+            assert_eq!(limiter.admit(&ip("::1")), Admission::Welcome);
+            assert!(limiter.admit_tip(&ip("::1")));
+        }
+    }
+
+    #[test]
+    fn ipv6_neighbors_share_buckets() {
+        let limiter = RateLimiter::new(Limit { per_minute: 1 }, Limit::unlimited(), vec![], vec![]);
+        assert_eq!(limiter.admit(&ip("2001:db8::1")), Admission::Welcome);
+        assert_eq!(limiter.admit(&ip("2001:db8::ffff:2")), Admission::SlowDown);
+        assert_eq!(limiter.admit(&ip("2001:db8:0:1::1")), Admission::Welcome);
+
+        assert_eq!(limiter.admit(&ip("192.0.2.1")), Admission::Welcome);
+        assert_eq!(limiter.admit(&ip("::ffff:192.0.2.1")), Admission::SlowDown);
+    }
+
+    #[test]
+    fn the_quietest_are_forgotten_first() {
+        let limiter = RateLimiter::new(Limit { per_minute: 1 }, Limit::unlimited(), vec![], vec![]);
+        limiter.pond.lock().unwrap().max_buckets = 2;
+
+        assert_eq!(limiter.admit(&ip("192.0.2.1")), Admission::Welcome);
+        assert_eq!(limiter.admit(&ip("192.0.2.2")), Admission::Welcome);
+        assert_eq!(limiter.admit(&ip("192.0.2.1")), Admission::SlowDown);
+        // Makes room by forgetting .2, which has been quiet longest
+        assert_eq!(limiter.admit(&ip("192.0.2.3")), Admission::Welcome);
+        assert_eq!(limiter.pond.lock().unwrap().buckets.len(), 2);
+        assert_eq!(limiter.admit(&ip("192.0.2.1")), Admission::SlowDown);
+        assert_eq!(limiter.admit(&ip("192.0.2.2")), Admission::Welcome);
+
+        // However much goes on, only so many takes are remembered
+        for _ in 0..100 {
+            limiter.admit(&ip("192.0.2.1"));
+        }
+        assert!(limiter.pond.lock().unwrap().takes.len() <= 4);
+    }
+}
@@ -0,0 +1,28 @@
+// Somewhere for tests to write to that stays readable once whatever was writing is done with it
+
+use std::io::{self,Write};
+use std::sync::{Arc,Mutex};
+
+
+#[derive(Clone)]
+pub struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Shared {
+    pub fn new() -> Shared {
+        Shared(Arc::new(Mutex::new(Vec::new())))
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+// A menu of how frog is doing, hidden at a selector of the operator's choosing and only shown
+// to addresses they trust. Everyone else is told it isn't there.
+
+use std::net::IpAddr;
+use std::time::{Duration,Instant};
+
+use cidr::Cidr;
+use metrics::Metrics;
+use protocol::Path;
+use the_impl_ya_dummy::Listing;
+
+
+// How many of each top list to show
+const TOP: usize = 10;
+
+#[derive(Clone,Debug)]
+pub struct StatsConfig {
+    pub selector: String,
+    pub allow: Vec<Cidr>,
+}
+
+impl StatsConfig {
+    pub fn shows(&self, peer: &IpAddr, path: &Path) -> bool {
+        path.val().trim_right_matches('/') == self.selector.trim_right_matches('/') &&
+            self.allow.iter().any(|cidr| cidr.contains(peer))
+    }
+}
+
+fn how_long(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}D {}H {}M {}S", secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
+
+// Whatever gophers and errors say has to fit on one info line
+pub fn one_line(text: &str) -> String {
+    text.replace(|c| c == '\t' || c == '\r' || c == '\n', " ")
+}
+
+fn top_list(lines: &mut Vec<String>, heading: &str, counts: Vec<(String, u64)>) {
+    lines.push(format!("\n{}", heading));
+    if counts.is_empty() {
+        lines.push("  NONE YET.".to_string());
+    }
+    for (what, count) in counts {
+        let what = if what.is_empty() { "(THE MAIN MENU)".to_string() } else { what };
+        lines.push(format!("  {:>6}  {}", count, one_line(&what)));
+    }
+}
+
+pub fn stats(started: Instant, metrics: &Metrics) -> Listing {
+    let mut lines = vec![
+        "FROG STATS.".to_string(),
+        format!("UP FOR {}.", how_long(started.elapsed())),
+        format!("{} REQUESTS.", metrics.total_requests()),
+    ];
+    lines.push(match metrics.tips_last_refreshed() {
+        Some((ago, count)) => format!("{} TIPS, REFRESHED {} AGO.", count, how_long(ago)),
+        None => "NO TIPS REFRESHED YET.".to_string(),
+    });
+
+    top_list(&mut lines, "TOP SELECTORS.", metrics.top_selectors(TOP));
+    top_list(&mut lines, "TOP TIP SEARCHES.", metrics.top_searches("tips", TOP));
+    top_list(&mut lines, "TOP GENUINEFROG SEARCHES.", metrics.top_searches("genuine_frog", TOP));
+
+    lines.push("\nRECENT ERRORS.".to_string());
+    let errors = metrics.recent_errors();
+    if errors.is_empty() {
+        lines.push("  NONE. GOOD FROG.".to_string());
+    }
+    for (when, why) in errors.into_iter().rev() {
+        lines.push(format!("  {} {}", when, one_line(&why)));
+    }
+
+    Listing::lines(lines)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use metrics::Metrics;
+    use protocol::{Menu,MenuItem,Path};
+    use super::{stats,StatsConfig};
+
+
+    #[test]
+    fn only_trusted_addresses_see_stats() {
+        let config = StatsConfig {
+            selector: "/STATS".to_string(),
+            allow: vec!["127.0.0.0/8".parse().unwrap()],
+        };
+        assert!(config.shows(&"127.0.0.1".parse().unwrap(), &Path::from("/STATS")));
+        assert!(config.shows(&"127.0.0.1".parse().unwrap(), &Path::from("/STATS/")));
+        assert!(!config.shows(&"192.0.2.1".parse().unwrap(), &Path::from("/STATS")));
+        assert!(!config.shows(&"127.0.0.1".parse().unwrap(), &Path::from("/DOCS")));
+    }
+
+    #[test]
+    fn stats_are_info_lines() {
+        let metrics = Metrics::new();
+        metrics.request("/TIP/SEARCH", "tips");
+        metrics.searched("tips", "toy");
+        metrics.remember_error("Line too big error");
+        metrics.remember_error("FROG\tSAYS\r\nRIBBIT");
+
+        let descs: Vec<String> = stats(Instant::now(), &metrics).items().into_iter().map(|item| match item {
+            MenuItem::Info {desc} => desc,
+            item => panic!("NOT AN INFO LINE: {:?}", item),
+        }).collect();
+        assert_eq!(&descs[..4], &["FROG STATS.", "UP FOR 0D 0H 0M 0S.", "1 REQUESTS.", "NO TIPS REFRESHED YET."]);
+        assert!(descs.contains(&"       1  /TIP/SEARCH".to_string()));
+        assert!(descs.contains(&"       1  TOY".to_string()));
+        assert!(descs.iter().any(|desc| desc.ends_with(" Line too big error")));
+        assert!(descs.iter().any(|desc| desc.ends_with(" FROG SAYS  RIBBIT")));
+    }
+}
@@ -1,39 +1,196 @@
 mod genuine_frog_source {
-    use protocol::{Menu,MenuItem,Path,Selected};
-    use super::menu::{Source,MenuItemIter};
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    use protocol::{MenuItem,Path,Selected};
+    use stats;
+    use super::menu::{Listing,Source,MenuItemIter};
 
     static SEARCH_PATH: &'static str = "/GENUINEFROG";
     static WE_REGRET_TO_INFORM_YOU: &'static str = "FROG SYSTEMS REGRETS TO INFORM YOU THAT YOU HAVE A COUNTERFEIT FROG. PLEASE CALL +1 (415) FROG-SYS TO TALK TO OUR SUPPORT STAFF.";
+    // Used unless the hole names a registry of its own
+    static REGISTRY: &'static str = include_str!("../txt/GENUINE_FROG_REGISTRY");
+
+    // The serial number prefix of every model in FROG_MODELS, and whether it's an Oceania model
+    static MODELS: &'static [(&'static str, &'static str, bool)] = &[
+        ("FR", "FROG", false),
+        ("FN", "FROG NANO", false),
+        ("FJ", "FROG JUMBO", false),
+        ("FC", "FROG CLASSIC", false),
+        ("F10", "FR-10", false),
+        ("FT", "FROG TOUCH", false),
+        ("RT", "REACH OUT AND TOUCH FROG", false),
+        ("PF", "PERSONAL FROG", false),
+        ("WF", "WIKI FROG", true),
+        ("FK", "FROG KIWI", true),
+    ];
+
+    // What a serial number says about its frog, whether or not it was ever issued
+    #[derive(Debug,Eq,PartialEq)]
+    struct Serial {
+        model: &'static str,
+        oceania: bool,
+        year: u32,
+        week: u32,
+    }
 
-    struct SearchResultsMenu {
+    #[derive(Debug,Eq,PartialEq)]
+    enum Verdict {
+        Genuine(Serial),
+        NeverIssued(Serial),
+        Counterfeit(&'static str),
     }
 
-    impl Menu for SearchResultsMenu {
-        fn items(&self) -> Vec<MenuItem> {
-            vec![MenuItem::Text {
-                path: Path::from(SEARCH_PATH),
-                desc: WE_REGRET_TO_INFORM_YOU.to_string(),
-            }]
+    // Luhn, over the batch and unit digits
+    fn check_digit(digits: &str) -> u32 {
+        let sum: u32 = digits.chars()
+                             .rev()
+                             .filter_map(|c| c.to_digit(10))
+                             .enumerate()
+                             .map(|(i, d)| match (i % 2, d * 2) {
+                                 (0, doubled) if doubled > 9 => doubled - 9,
+                                 (0, doubled) => doubled,
+                                 _ => d,
+                             })
+                             .sum();
+        (10 - sum % 10) % 10
+    }
+
+    // Serial numbers look like FC-9301-00042-9: the model, the batch (year and week), the unit and a check digit
+    fn parse(serial: &str) -> Result<Serial, &'static str> {
+        let parts: Vec<&str> = serial.split('-').collect();
+        if parts.len() != 4 {
+            return Err("THAT IS NOT A FROG SERIAL NUMBER.");
+        }
+
+        let (prefix, batch, unit, check) = (parts[0], parts[1], parts[2], parts[3]);
+        let digits = format!("{}{}{}", batch, unit, check);
+        if batch.len() != 4 || unit.len() != 5 || check.len() != 1 || !digits.chars().all(|c| c.is_digit(10)) {
+            return Err("THAT IS NOT A FROG SERIAL NUMBER.");
+        }
+
+        let &(_, model, oceania) = match MODELS.iter().find(|&&(known, _, _)| known == prefix) {
+            Some(model) => model,
+            None => return Err("FROG SYSTEMS HAS NEVER MADE THAT MODEL."),
+        };
+
+        if check.parse() != Ok(check_digit(&digits[..9])) {
+            return Err("THE SERIAL NUMBER DOES NOT ADD UP.");
+        }
+
+        let year: u32 = batch[..2].parse().unwrap();
+        let week: u32 = batch[2..].parse().unwrap();
+        if week < 1 || week > 53 {
+            return Err("FROG SYSTEMS NEVER MADE THAT BATCH.");
+        }
+
+        Ok(Serial {
+            model: model,
+            oceania: oceania,
+            year: if year >= 70 { 1900 + year } else { 2000 + year },
+            week: week,
+        })
+    }
+
+    fn verify(serial: &str, registry: &str) -> Verdict {
+        let serial = serial.trim().to_uppercase();
+        match parse(&serial) {
+            Ok(parsed) => {
+                let issued = registry.lines()
+                                     .map(|line| line.trim())
+                                     .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                                     .any(|line| line.to_uppercase() == serial);
+                if issued {
+                    Verdict::Genuine(parsed)
+                } else {
+                    Verdict::NeverIssued(parsed)
+                }
+            },
+            Err(why) => Verdict::Counterfeit(why),
+        }
+    }
+
+    fn describe(serial: &str, verdict: &Verdict) -> Vec<String> {
+        // Whatever the gopher typed has to fit on one info line
+        let mut lines = vec![format!("SERIAL NUMBER: {}", stats::one_line(serial.trim()).to_uppercase())];
+
+        match verdict {
+            &Verdict::Genuine(ref parsed) | &Verdict::NeverIssued(ref parsed) => {
+                lines.push(format!("MODEL: {}", parsed.model));
+                lines.push(format!("BATCH: WEEK {} OF {}", parsed.week, parsed.year));
+            },
+            &Verdict::Counterfeit(_) => {},
+        }
+
+        match verdict {
+            &Verdict::Genuine(Serial {oceania: false, ..}) => {
+                lines.push("FIRMWARE: ELIGIBLE FOR FROG V2 FIRMWARE.".to_string());
+                lines.push("STATUS: GENUINE FROG. THANK YOU FOR CHOOSING FROG SYSTEMS.".to_string());
+            },
+            &Verdict::Genuine(Serial {oceania: true, ..}) => {
+                lines.push("FIRMWARE: NOT ELIGIBLE FOR FROG V2 FIRMWARE. DO NOT UPDATE OCEANIA MODELS.".to_string());
+                lines.push("STATUS: GENUINE FROG. THANK YOU FOR CHOOSING FROG SYSTEMS.".to_string());
+            },
+            &Verdict::NeverIssued(_) => {
+                lines.push("STATUS: COUNTERFEIT. FROG SYSTEMS NEVER ISSUED THAT SERIAL NUMBER.".to_string());
+                lines.push(WE_REGRET_TO_INFORM_YOU.to_string());
+            },
+            &Verdict::Counterfeit(why) => {
+                lines.push(format!("STATUS: COUNTERFEIT. {}", why));
+                lines.push(WE_REGRET_TO_INFORM_YOU.to_string());
+            },
         }
+
+        lines
     }
 
     pub struct GenuineFrogSource {
+        registry: Option<PathBuf>,
     }
 
     impl GenuineFrogSource {
-        pub fn new() -> GenuineFrogSource {
-            GenuineFrogSource {}
+        pub fn new(registry: Option<PathBuf>) -> GenuineFrogSource {
+            GenuineFrogSource {
+                registry: registry,
+            }
+        }
+
+        // Read every time, so newly issued frogs are genuine right away
+        fn registry(&self) -> Option<String> {
+            let file = match self.registry {
+                Some(ref file) => file,
+                None => return Some(REGISTRY.to_string()),
+            };
+
+            let mut registry = String::new();
+            match File::open(file).and_then(|mut f| f.read_to_string(&mut registry)) {
+                Ok(_) => Some(registry),
+                Err(why) => {
+                    warn!("COULD NOT READ THE FROG REGISTRY AT {:?}: {}", file, why);
+                    None
+                },
+            }
         }
     }
 
     impl Source for GenuineFrogSource {
         fn find(&self, path: &Path) -> Option<Selected> {
             let val = path.val();
-            if val.starts_with(SEARCH_PATH) {
-                Some(Selected::TempMenu(Box::new(SearchResultsMenu {})))
-            } else {
-                None
+            if !val.starts_with(SEARCH_PATH) {
+                return None;
             }
+
+            let lines = match (path.extra(), self.registry()) {
+                (None, _) => vec!["TYPE IN THE SERIAL NUMBER FROM THE BOTTOM OF YOUR FROG.".to_string()],
+                (Some(serial), Some(registry)) => describe(serial, &verify(serial, &registry)),
+                (Some(_), None) => vec!["FROG SYSTEMS CANNOT CHECK SERIAL NUMBERS RIGHT NOW. PLEASE TRY AGAIN LATER.".to_string()],
+            };
+            Some(Selected::TempMenu(Box::new(Listing::lines(lines))))
+        }
+
+        fn kind(&self) -> &'static str {
+            "genuine_frog"
         }
 
         fn menu_items(&self) -> MenuItemIter {
@@ -43,6 +200,66 @@ mod genuine_frog_source {
             }])
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{MODELS,REGISTRY,Serial,Verdict,check_digit,verify,describe};
+
+
+        const FROG_MODELS: &'static str = include_str!("../txt/FROG_MODELS");
+
+        #[test]
+        fn every_model_has_a_prefix() {
+            let listed: Vec<&str> = FROG_MODELS.lines().filter(|line| !line.is_empty()).collect();
+            assert_eq!(listed.len(), MODELS.len());
+
+            for &(_, model, oceania) in MODELS {
+                let line = listed.iter()
+                                 .find(|line| **line == model || line.starts_with(&format!("{} (", model)))
+                                 .expect(model);
+                assert_eq!(line.contains("(OCEANIA MODEL)"), oceania);
+            }
+        }
+
+        #[test]
+        fn check_digits_are_luhn() {
+            assert_eq!(check_digit("930100042"), 9);
+            assert_eq!(check_digit("943300420"), 8);
+        }
+
+        #[test]
+        fn serials_are_verified() {
+            assert_eq!(verify("fc-9301-00042-9 ", REGISTRY),
+                       Verdict::Genuine(Serial {model: "FROG CLASSIC", oceania: false, year: 1993, week: 1}));
+            assert_eq!(verify("WF-9433-00420-8", REGISTRY),
+                       Verdict::Genuine(Serial {model: "WIKI FROG", oceania: true, year: 1994, week: 33}));
+
+            // Adds up, but was never made
+            assert_eq!(verify("FC-9301-00043-7", REGISTRY),
+                       Verdict::NeverIssued(Serial {model: "FROG CLASSIC", oceania: false, year: 1993, week: 1}));
+
+            assert!(match verify("FC-9301-00042-1", REGISTRY) { Verdict::Counterfeit(_) => true, _ => false });
+            assert!(match verify("TD-9301-00042-9", REGISTRY) { Verdict::Counterfeit(_) => true, _ => false });
+            assert!(match verify("FC-9399-00042-6", REGISTRY) { Verdict::Counterfeit(_) => true, _ => false });
+            assert!(match verify("FROG", REGISTRY) { Verdict::Counterfeit(_) => true, _ => false });
+        }
+
+        #[test]
+        fn only_non_oceania_frogs_get_v2() {
+            let classic = describe("FC-9301-00042-9", &verify("FC-9301-00042-9", REGISTRY));
+            assert!(classic.contains(&"FIRMWARE: ELIGIBLE FOR FROG V2 FIRMWARE.".to_string()));
+
+            let kiwi = describe("FK-9433-00421-6", &verify("FK-9433-00421-6", REGISTRY));
+            assert!(kiwi.iter().any(|line| line.starts_with("FIRMWARE: NOT ELIGIBLE")));
+            assert!(kiwi.last().unwrap().starts_with("STATUS: GENUINE"));
+        }
+
+        #[test]
+        fn serials_stay_on_one_line() {
+            let sneaky = describe("FROG\t/\tevil.example\t70\r\n1EVIL", &verify("FROG", REGISTRY));
+            assert_eq!(sneaky[0], "SERIAL NUMBER: FROG / EVIL.EXAMPLE 70  1EVIL");
+        }
+    }
 }
 
 mod bogus_source {
@@ -68,6 +285,10 @@ mod bogus_source {
             None
         }
 
+        fn kind(&self) -> &'static str {
+            "bogus"
+        }
+
         fn menu_items(&self) -> MenuItemIter {
             MenuItemIter::new(vec![MenuItem::Text {
                 path: self.path.clone(),
@@ -102,6 +323,10 @@ mod url_source {
             None
         }
 
+        fn kind(&self) -> &'static str {
+            "url"
+        }
+
         fn menu_items(&self) -> MenuItemIter {
             MenuItemIter::new(vec![MenuItem::JohnGoerzenUrl {
                 url: self.url.clone(),
@@ -111,6 +336,556 @@ mod url_source {
     }
 }
 
+mod menu_source {
+    use protocol::{MenuItem,Path,Selected};
+    use super::menu::{self,AnyMenu,Later,Source,MenuItemIter};
+
+    pub struct MenuSource {
+        path: Path,
+        desc: String,
+        menu: AnyMenu,
+    }
+
+    impl MenuSource {
+        pub fn new<S: Into<String>>(path: Path, desc: S, menu: AnyMenu) -> MenuSource {
+            MenuSource {
+                path: path,
+                desc: desc.into(),
+                menu: menu,
+            }
+        }
+
+        fn owns(&self, path: &Path) -> bool {
+            let prefix = self.path.val().trim_right_matches('/');
+            let val = path.val();
+            val.starts_with(prefix) && match val[prefix.len()..].chars().next() {
+                None | Some('/') => true,
+                _ => false,
+            }
+        }
+    }
+
+    impl Source for MenuSource {
+        fn find(&self, path: &Path) -> Option<Selected> {
+            self.find_kind(path).map(|(selected, _)| selected)
+        }
+
+        fn kind(&self) -> &'static str {
+            "menu"
+        }
+
+        // Whatever is in the menu is counted as whatever kind found it
+        fn find_kind(&self, path: &Path) -> Option<(Selected, &'static str)> {
+            if !self.owns(path) {
+                return None;
+            }
+
+            if menu::same_path(path, &self.path) {
+                Some((Selected::ForeverMenu(&self.menu), self.kind()))
+            } else {
+                self.menu.find_kind(path)
+            }
+        }
+
+        fn menu_items(&self) -> MenuItemIter {
+            MenuItemIter::new(vec![MenuItem::Directory {
+                path: self.path.clone(),
+                desc: self.desc.clone(),
+            }])
+        }
+
+        fn is_costly(&self, path: &Path) -> bool {
+            self.owns(path) && self.menu.is_costly(path)
+        }
+
+        fn find_later(&self, path: &Path) -> Option<(Later, &'static str)> {
+            if !self.owns(path) {
+                return None;
+            }
+            self.menu.find_later(path)
+        }
+
+        fn describe(&self, path: &Path) -> Option<String> {
+            if !self.owns(path) {
+                None
+            } else if menu::same_path(path, &self.path) {
+                Some(self.desc.clone())
+            } else {
+                self.menu.describe(path)
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use protocol::{Path,Selected};
+        use super::super::menu::{AnyMenu,Source};
+        use super::super::text_source::TextSource;
+        use super::MenuSource;
+
+
+        fn docs() -> MenuSource {
+            let mut menu = AnyMenu::new();
+            menu.push(TextSource::new(Path::from("/DOCS/README"), "README.", "FROG."));
+            MenuSource::new(Path::from("/DOCS/"), "DOCS.", menu)
+        }
+
+        fn kind(found: Option<(Selected, &'static str)>) -> Option<&'static str> {
+            found.map(|(_, kind)| kind)
+        }
+
+        #[test]
+        fn menus_own_their_prefix() {
+            let docs = docs();
+            assert_eq!(kind(docs.find_kind(&Path::from("/DOCS/README"))), Some("text"));
+            assert_eq!(kind(docs.find_kind(&Path::from("/DOCS/NOTHING"))), None);
+            assert!(docs.find(&Path::from("/DOCSTRINGS")).is_none());
+            assert!(docs.find(&Path::from("/README")).is_none());
+        }
+
+        #[test]
+        fn trailing_slashes_make_no_difference() {
+            let docs = docs();
+            for path in &["/DOCS", "/DOCS/"] {
+                assert!(match docs.find(&Path::from(*path)) { Some(Selected::ForeverMenu(_)) => true, _ => false });
+            }
+        }
+    }
+}
+
+mod file_system_source {
+    use hyper::Url;
+
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io;
+    use std::io::Read;
+    use std::path::{Component,PathBuf};
+
+    use protocol::{self,MenuItem,Path,Selected};
+    use super::menu::{Listing,Source,MenuItemIter};
+
+
+    static GOPHERMAP: &'static str = "gophermap";
+
+    enum ItemType {
+        Text,
+        Directory,
+        // Which of the binary types
+        Binary(char),
+    }
+
+    impl ItemType {
+        // Anything we can't name is left out of listings entirely
+        fn detect(path: &::std::path::Path) -> Option<ItemType> {
+            if path.is_dir() {
+                return Some(ItemType::Directory);
+            }
+
+            let ext = path.extension()
+                          .and_then(|e| e.to_str())
+                          .map(|e| e.to_lowercase());
+
+            match ext.as_ref().map(|e| e.as_str()) {
+                None | Some("txt") | Some("md") | Some("text") | Some("asc") => Some(ItemType::Text),
+                _ => path.to_str().and_then(protocol::binary_type).map(ItemType::Binary),
+            }
+        }
+    }
+
+    pub struct FileSystemSource {
+        path: Path,
+        desc: String,
+        root: PathBuf,
+    }
+
+    impl FileSystemSource {
+        pub fn new<S: Into<String>, P: Into<PathBuf>>(path: Path, desc: S, root: P) -> FileSystemSource {
+            FileSystemSource {
+                path: path,
+                desc: desc.into(),
+                root: root.into(),
+            }
+        }
+
+        fn prefix(&self) -> &str {
+            self.path.val().trim_right_matches('/')
+        }
+
+        // The part of the selector below our own, or None if it isn't ours
+        fn relative<'b>(&self, val: &'b str) -> Option<&'b str> {
+            let prefix = self.prefix();
+            if !val.starts_with(prefix) {
+                return None;
+            }
+
+            let rest = &val[prefix.len()..];
+            if rest.is_empty() || rest.starts_with('/') {
+                Some(rest.trim_matches('/'))
+            } else {
+                None
+            }
+        }
+
+        fn resolve(&self, relative: &str) -> Option<PathBuf> {
+            let mut resolved = self.root.clone();
+
+            for part in relative.split('/').filter(|p| !p.is_empty()) {
+                let mut components = ::std::path::Path::new(part).components();
+                match (components.next(), components.next()) {
+                    (Some(Component::Normal(c)), None) if !part.starts_with('.') => resolved.push(c),
+                    _ => {
+                        warn!("REFUSING TO WANDER TO '{}'.", relative);
+                        return None;
+                    },
+                }
+            }
+
+            // Symlinks may point anywhere, so check where we really ended up
+            let root = match fs::canonicalize(&self.root) {
+                Ok(root) => root,
+                Err(why) => {
+                    warn!("FILESYSTEM ROOT {:?} IS MISSING: {}", self.root, why);
+                    return None;
+                },
+            };
+
+            match fs::canonicalize(&resolved) {
+                Ok(ref real) if real.starts_with(&root) => Some(real.clone()),
+                Ok(real) => {
+                    warn!("REFUSING TO FOLLOW '{}' OUT TO {:?}.", relative, real);
+                    None
+                },
+                Err(_) => None,
+            }
+        }
+
+        fn selector(&self, relative: &str, name: &str) -> Path {
+            if relative.is_empty() {
+                Path::from(format!("{}/{}", self.prefix(), name))
+            } else {
+                Path::from(format!("{}/{}/{}", self.prefix(), relative, name))
+            }
+        }
+
+        fn list(&self, relative: &str, dir: &::std::path::Path) -> io::Result<Vec<MenuItem>> {
+            let mut entries = vec![];
+            for entry in try!(fs::read_dir(dir)) {
+                let entry = try!(entry);
+                if let Ok(name) = entry.file_name().into_string() {
+                    if !name.starts_with('.') && name != GOPHERMAP {
+                        entries.push((name, entry.path()));
+                    }
+                }
+            }
+            entries.sort();
+
+            Ok(entries.into_iter()
+                      .filter_map(|(name, path)| {
+                          let selector = self.selector(relative, &name);
+                          match ItemType::detect(&path) {
+                              Some(ItemType::Text) => Some(MenuItem::Text {path: selector, desc: name}),
+                              Some(ItemType::Directory) => Some(MenuItem::Directory {path: selector, desc: name}),
+                              Some(ItemType::Binary(item_type)) => MenuItem::binary(item_type, selector, name),
+                              None => None,
+                          }
+                      })
+                      .collect())
+        }
+
+        // Reads a gophermap the way Bucktooth and Gophernicus do: tabbed lines are
+        // items, untabbed lines are info, `*` lists the directory and `.` stops.
+        fn gophermap(&self, relative: &str, dir: &::std::path::Path, map: &str) -> io::Result<Vec<MenuItem>> {
+            let mut items = vec![];
+
+            for line in map.lines() {
+                let line = line.trim_right_matches('\r');
+
+                if line == "." {
+                    break;
+                } else if line == "*" {
+                    items.extend(try!(self.list(relative, dir)));
+                } else if line.starts_with('#') {
+                    continue;
+                } else if !line.contains('\t') {
+                    items.push(MenuItem::Info {desc: line.to_string()});
+                } else {
+                    let mut fields = line.split('\t');
+                    let display = fields.next().unwrap_or("");
+                    let selector = fields.next().unwrap_or("");
+                    let host = fields.next().unwrap_or("");
+                    let port = fields.next().unwrap_or("");
+
+                    let mut chars = display.chars();
+                    let item_type = chars.next();
+                    let desc = chars.as_str().to_string();
+
+                    // URLs and info lines go wherever they go, whatever host they carry
+                    let local = host.is_empty() || host == "+";
+                    match item_type {
+                        Some(item_type) if !local && item_type != 'h' && item_type != 'i' => {
+                            let port = match port {
+                                "" | "+" => 70,
+                                port => match port.parse() {
+                                    Ok(port) => port,
+                                    Err(_) => {
+                                        warn!("SKIPPING GOPHERMAP LINE WITH A BAD PORT: {}", line);
+                                        continue;
+                                    },
+                                },
+                            };
+                            items.push(MenuItem::Elsewhere {
+                                item_type: item_type,
+                                path: Path::from(selector),
+                                desc: desc,
+                                host: host.to_string(),
+                                port: port,
+                            });
+                            continue;
+                        },
+                        _ => {},
+                    }
+
+                    let selector = if selector.is_empty() {
+                        self.selector(relative, &desc)
+                    } else if selector.starts_with('/') || selector.starts_with("URL:") {
+                        Path::from(selector)
+                    } else {
+                        self.selector(relative, selector)
+                    };
+
+                    let item = match item_type {
+                        Some('0') => MenuItem::Text {path: selector, desc: desc},
+                        Some('1') => MenuItem::Directory {path: selector, desc: desc},
+                        Some('7') => MenuItem::Search {path: selector, desc: desc},
+                        Some('i') => MenuItem::Info {desc: desc},
+                        Some(item_type @ '9') |
+                        Some(item_type @ 'g') |
+                        Some(item_type @ 'I') |
+                        Some(item_type @ '5') |
+                        Some(item_type @ 's') => MenuItem::binary(item_type, selector, desc).unwrap(),
+                        Some('h') => match Url::parse(selector.val().trim_left_matches("URL:")) {
+                            Ok(url) => MenuItem::JohnGoerzenUrl {url: url, desc: desc},
+                            Err(why) => {
+                                warn!("SKIPPING GOPHERMAP LINE WITH A BAD URL ({}): {}", why, line);
+                                continue;
+                            },
+                        },
+                        _ => {
+                            warn!("SKIPPING GOPHERMAP LINE OF AN UNSUPPORTED TYPE: {}", line);
+                            continue;
+                        },
+                    };
+
+                    items.push(item);
+                }
+            }
+
+            Ok(items)
+        }
+
+        fn menu(&self, relative: &str, dir: &::std::path::Path) -> io::Result<Vec<MenuItem>> {
+            let map_path = dir.join(GOPHERMAP);
+            if map_path.is_file() {
+                let mut map = String::new();
+                try!(try!(fs::File::open(&map_path)).read_to_string(&mut map));
+                self.gophermap(relative, dir, &map)
+            } else {
+                self.list(relative, dir)
+            }
+        }
+
+        // How big each item that leads to one of our files is
+        fn sizes(&self, items: &Vec<MenuItem>) -> HashMap<String, usize> {
+            items.iter()
+                 .filter_map(|item| item.path())
+                 .filter_map(|path| {
+                     let meta = self.relative(path.val())
+                                    .and_then(|relative| self.resolve(relative))
+                                    .and_then(|real| fs::metadata(real).ok());
+                     match meta {
+                         Some(ref meta) if meta.is_file() => Some((path.val().clone(), meta.len() as usize)),
+                         _ => None,
+                     }
+                 })
+                 .collect()
+        }
+
+        fn text(&self, file: &::std::path::Path) -> io::Result<String> {
+            String::from_utf8(try!(super::read_file(file)))
+                .map_err(|why| io::Error::new(io::ErrorKind::InvalidData, why))
+        }
+    }
+
+    impl Source for FileSystemSource {
+        fn find(&self, path: &Path) -> Option<Selected> {
+            let relative = match self.relative(path.val()) {
+                Some(relative) => relative,
+                None => return None,
+            };
+
+            let real = match self.resolve(relative) {
+                Some(real) => real,
+                None => return None,
+            };
+
+            let res = if real.is_dir() {
+                self.menu(relative, &real)
+                    .map(|items| {
+                        let sizes = self.sizes(&items);
+                        Selected::TempMenu(Box::new(Listing::sized(items, sizes)))
+                    })
+            } else {
+                match ItemType::detect(&real) {
+                    Some(ItemType::Text) => self.text(&real).map(|text| Selected::Text(Box::new(text))),
+                    Some(ItemType::Binary(_)) => super::read_file(&real).map(|bytes| Selected::Binary(Box::new(bytes))),
+                    _ => return None,
+                }
+            };
+
+            match res {
+                Ok(selected) => Some(selected),
+                Err(why) => {
+                    warn!("COULD NOT READ {:?}: {}", real, why);
+                    Some(Selected::Failed(Box::new(format!("FROG COULD NOT READ {}", path))))
+                },
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "files"
+        }
+
+        // Files are called by their names, as they are in listings without a gophermap
+        fn describe(&self, path: &Path) -> Option<String> {
+            match self.relative(path.val()) {
+                Some("") => Some(self.desc.clone()),
+                Some(relative) => self.resolve(relative)
+                                      .and_then(|_| relative.rsplit('/').next())
+                                      .map(|name| name.to_string()),
+                None => None,
+            }
+        }
+
+        fn menu_items(&self) -> MenuItemIter {
+            MenuItemIter::new(vec![MenuItem::Directory {
+                path: self.path.clone(),
+                desc: self.desc.clone(),
+            }])
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::env;
+        use std::fs;
+        use std::io::Write;
+        use std::path::PathBuf;
+
+        use protocol::{MenuItem,Path,Selected};
+        use super::super::menu::Source;
+        use super::FileSystemSource;
+
+
+        fn hole(name: &str) -> PathBuf {
+            let root = env::temp_dir().join(format!("frog_gopher_{}", name));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("sub")).unwrap();
+            fs::File::create(root.join("JOB_OPENINGS")).unwrap().write_all(b"NONE.").unwrap();
+            fs::File::create(root.join("sub").join("notes.txt")).unwrap().write_all(b"RIBBIT.").unwrap();
+            fs::File::create(root.join("photo.jpg")).unwrap().write_all(b"\xff\xd8").unwrap();
+            fs::File::create(root.join("frog.xyz")).unwrap().write_all(b"?").unwrap();
+            root
+        }
+
+        fn items(selected: Option<Selected>) -> Vec<MenuItem> {
+            match selected {
+                Some(Selected::TempMenu(menu)) => menu.items(),
+                _ => panic!("expected a menu"),
+            }
+        }
+
+        fn text(selected: Option<Selected>) -> String {
+            match selected {
+                Some(Selected::Text(text)) => *text,
+                _ => panic!("expected text"),
+            }
+        }
+
+        #[test]
+        fn lists_directories() {
+            let source = FileSystemSource::new(Path::from("/FILES"), "FILES.", hole("lists"));
+            let listing = format!("{:?}", items(source.find(&Path::from("/FILES"))));
+            assert!(listing.contains("Text { path: Path { val: \"/FILES/JOB_OPENINGS\""));
+            assert!(listing.contains("Directory { path: Path { val: \"/FILES/sub\""));
+            assert!(listing.contains("Image { path: Path { val: \"/FILES/photo.jpg\""));
+            assert!(!listing.contains("frog.xyz"));
+
+            assert_eq!(text(source.find(&Path::from("/FILES/sub/notes.txt"))), "RIBBIT.");
+            assert!(match source.find(&Path::from("/FILES/photo.jpg")) {
+                Some(Selected::Binary(bytes)) => *bytes == b"\xff\xd8".to_vec(),
+                _ => false,
+            });
+            assert!(source.find(&Path::from("/FILES/frog.xyz")).is_none());
+            assert!(source.find(&Path::from("/FILESYSTEM")).is_none());
+        }
+
+        #[test]
+        fn honors_gophermaps() {
+            let root = hole("gophermap");
+            fs::File::create(root.join("gophermap")).unwrap()
+                .write_all(b"WELCOME, FRIEND\n0JOBS\tJOB_OPENINGS\n1ELSEWHERE\t/DOCS\nIFROG, UP CLOSE\tphoto.jpg\n*\n.\nIGNORED\n").unwrap();
+
+            let source = FileSystemSource::new(Path::from("/FILES"), "FILES.", root);
+            let listing = items(source.find(&Path::from("/FILES/")));
+            let listing = format!("{:?}", listing);
+            assert!(listing.starts_with("[Info { desc: \"WELCOME, FRIEND\" }, Text { path: Path { val: \"/FILES/JOB_OPENINGS\""));
+            assert!(listing.contains("Directory { path: Path { val: \"/DOCS\""));
+            assert!(listing.contains("Image { path: Path { val: \"/FILES/photo.jpg\", extra: None }, desc: \"FROG, UP CLOSE\""));
+            assert!(listing.contains("/FILES/sub"));
+            assert!(!listing.contains("IGNORED"));
+        }
+
+        #[test]
+        fn keeps_links_to_other_holes() {
+            let root = hole("elsewhere");
+            fs::File::create(root.join("gophermap")).unwrap()
+                .write_all(b"1FLOODGAP\t/\tgopher.floodgap.com\t70\n\
+                             0FROGS\t/frogs.txt\tfrogs.example\n\
+                             hFROG TIPS\tURL:https://frog.tips\tgopher.floodgap.com\t70\n\
+                             1BROKEN\t/\tfrogs.example\tEVERYWHERE\n").unwrap();
+
+            let source = FileSystemSource::new(Path::from("/FILES"), "FILES.", root);
+            let listing = format!("{:?}", items(source.find(&Path::from("/FILES"))));
+            assert!(listing.contains("Elsewhere { item_type: '1', path: Path { val: \"/\", extra: None }, desc: \"FLOODGAP\", \
+                                      host: \"gopher.floodgap.com\", port: 70 }"));
+            assert!(listing.contains("Elsewhere { item_type: '0', path: Path { val: \"/frogs.txt\", extra: None }, desc: \"FROGS\", \
+                                      host: \"frogs.example\", port: 70 }"));
+            assert!(listing.contains("JohnGoerzenUrl"));
+            assert!(!listing.contains("BROKEN"));
+        }
+
+        #[test]
+        fn refuses_to_leave_the_root() {
+            let root = hole("escape");
+            let source = FileSystemSource::new(Path::from("/FILES"), "FILES.", root.join("sub"));
+            assert!(source.find(&Path::from("/FILES/../JOB_OPENINGS")).is_none());
+            assert!(source.find(&Path::from("/FILES/sub/../../JOB_OPENINGS")).is_none());
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn refuses_to_follow_symlinks_out() {
+            let root = hole("symlink");
+            ::std::os::unix::fs::symlink(root.join("JOB_OPENINGS"), root.join("sub").join("sneaky")).unwrap();
+
+            let source = FileSystemSource::new(Path::from("/FILES"), "FILES.", root.join("sub"));
+            assert!(source.find(&Path::from("/FILES/sneaky")).is_none());
+            assert_eq!(text(source.find(&Path::from("/FILES/notes.txt"))), "RIBBIT.");
+        }
+    }
+}
+
 mod info_source {
     use protocol::{MenuItem,Path,Selected};
     use super::menu::{Source,MenuItemIter};
@@ -132,6 +907,10 @@ mod info_source {
             None
         }
 
+        fn kind(&self) -> &'static str {
+            "info"
+        }
+
         fn menu_items(&self) -> MenuItemIter {
             MenuItemIter::new(vec![MenuItem::Info {
                 desc: self.desc.clone(),
@@ -170,6 +949,18 @@ mod text_source {
             }
         }
 
+        fn kind(&self) -> &'static str {
+            "text"
+        }
+
+        fn size(&self, path: &Path) -> Option<usize> {
+            if self.path == *path {
+                Some(self.text.len())
+            } else {
+                None
+            }
+        }
+
         fn menu_items(&self) -> MenuItemIter {
             MenuItemIter::new(vec![MenuItem::Text {
                 path: self.path.clone(),
@@ -179,21 +970,371 @@ mod text_source {
     }
 }
 
+mod manual_source {
+    use protocol::{MenuItem,Path,Selected};
+    use super::menu::{Listing,Source,MenuItemIter};
+
+
+    static FOREWORD: &'static str = "FOREWORD";
+    static APPENDIX: &'static str = "APPENDIX";
+    static SEARCH: &'static str = "SEARCH";
+    static SEARCH_DESC: &'static str = "SEARCH THE MANUAL.";
+
+    struct Page {
+        path: Path,
+        title: String,
+        text: String,
+    }
+
+    pub struct ManualSource {
+        path: Path,
+        desc: String,
+        pages: Vec<Page>,
+        contents: Listing,
+    }
+
+    impl ManualSource {
+        // Pages come in as (file name, text), already in order
+        pub fn new<S: Into<String>>(path: Path, desc: S, pages: &Vec<(String, String)>) -> ManualSource {
+            let prefix = path.val().trim_right_matches('/').to_string();
+            let mut foreword = vec![];
+            let mut chapters = vec![];
+            let mut appendices = vec![];
+
+            for &(ref name, ref text) in pages {
+                if name == FOREWORD {
+                    foreword.push(MenuItem::Info {desc: text.trim_right().to_string()});
+                    continue;
+                }
+
+                let page = Page {
+                    path: Path::from(format!("{}/{}", prefix, name)),
+                    title: text.lines().next().unwrap_or(name).trim().to_string(),
+                    text: text.clone(),
+                };
+                if name.starts_with(APPENDIX) {
+                    appendices.push(page);
+                } else {
+                    chapters.push(page);
+                }
+            }
+
+            let mut items = foreword;
+            items.push(MenuItem::Search {
+                path: Path::from(format!("{}/{}", prefix, SEARCH)),
+                desc: SEARCH_DESC.to_string(),
+            });
+            for (heading, pages) in vec![("\nCHAPTERS.", &chapters), ("\nAPPENDICES.", &appendices)] {
+                if pages.is_empty() {
+                    continue;
+                }
+                items.push(MenuItem::Info {desc: heading.to_string()});
+                items.extend(pages.iter().map(|page| MenuItem::Text {
+                    path: page.path.clone(),
+                    desc: page.title.clone(),
+                }));
+            }
+
+            chapters.extend(appendices);
+            let sizes = chapters.iter()
+                                .map(|page| (page.path.val().clone(), page.text.len()))
+                                .collect();
+            ManualSource {
+                path: Path::from(prefix),
+                desc: desc.into(),
+                pages: chapters,
+                contents: Listing::sized(items, sizes),
+            }
+        }
+
+        // Every line that mentions all of the terms, wherever it is
+        fn search(&self, terms: &str) -> Vec<MenuItem> {
+            let terms: Vec<String> = terms.split_whitespace().map(|term| term.to_uppercase()).collect();
+            let mut found = vec![];
+
+            for page in self.pages.iter() {
+                for line in page.text.lines().skip(1) {
+                    let upper = line.to_uppercase();
+                    if !line.trim().is_empty() && terms.iter().all(|term| upper.contains(term.as_str())) {
+                        found.push(MenuItem::Text {
+                            path: page.path.clone(),
+                            desc: format!("{}: {}", page.title, line.trim()),
+                        });
+                    }
+                }
+            }
+
+            if found.is_empty() {
+                found.push(MenuItem::Info {desc: format!("THE MANUAL SAYS NOTHING ABOUT {}.", terms.join(" "))});
+            }
+            found
+        }
+    }
+
+    impl Source for ManualSource {
+        fn find(&self, path: &Path) -> Option<Selected> {
+            let val = path.val().trim_right_matches('/');
+            if val == self.path.val() {
+                return Some(Selected::ForeverMenu(&self.contents));
+            }
+
+            if val == format!("{}/{}", self.path.val(), SEARCH) {
+                return match path.extra() {
+                    Some(terms) if !terms.trim().is_empty() =>
+                        Some(Selected::TempMenu(Box::new(self.contents.with_items(self.search(terms))))),
+                    _ => Some(Selected::ForeverMenu(&self.contents)),
+                };
+            }
+
+            self.pages.iter()
+                      .find(|page| page.path.val() == val)
+                      .map(|page| Selected::Text(Box::new(page.text.clone())))
+        }
+
+        fn kind(&self) -> &'static str {
+            "manual"
+        }
+
+        fn describe(&self, path: &Path) -> Option<String> {
+            let val = path.val().trim_right_matches('/');
+            if val == self.path.val() {
+                return Some(self.desc.clone());
+            }
+            if val == format!("{}/{}", self.path.val(), SEARCH) {
+                return Some(SEARCH_DESC.to_string());
+            }
+            self.pages.iter()
+                      .find(|page| page.path.val() == val)
+                      .map(|page| page.title.clone())
+        }
+
+        fn menu_items(&self) -> MenuItemIter {
+            MenuItemIter::new(vec![MenuItem::Directory {
+                path: self.path.clone(),
+                desc: self.desc.clone(),
+            }])
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use protocol::{MenuItem,Path,Selected};
+        use super::super::menu::Source;
+        use super::ManualSource;
+
+
+        fn manual() -> ManualSource {
+            let pages = vec![
+                ("APPENDIX_A".to_string(), "APPENDIX A. WARRANTY\n\nNONE WHATSOEVER.\n".to_string()),
+                ("CHAPTER_01".to_string(), "CHAPTER 1. UNPACKING\n\nOPEN THE BOX.\nTAKE OUT FROG.\n".to_string()),
+                ("CHAPTER_02".to_string(), "CHAPTER 2. CARE\n\nNEVER MICROWAVE FROG.\n".to_string()),
+                ("FOREWORD".to_string(), "FROG USER MANUAL.\n".to_string()),
+            ];
+            ManualSource::new(Path::from("/MANUAL/"), "MANUAL.", &pages)
+        }
+
+        fn items(selected: Option<Selected>) -> Vec<MenuItem> {
+            match selected {
+                Some(Selected::ForeverMenu(menu)) => menu.items(),
+                Some(Selected::TempMenu(menu)) => menu.items(),
+                _ => panic!("expected a menu"),
+            }
+        }
+
+        #[test]
+        fn contents_list_chapters_then_appendices() {
+            let manual = manual();
+            let contents = format!("{:?}", items(manual.find(&Path::from("/MANUAL"))));
+            assert!(contents.starts_with("[Info { desc: \"FROG USER MANUAL.\" }, Search { path: Path { val: \"/MANUAL/SEARCH\""));
+
+            let chapter = contents.find("\"CHAPTER 1. UNPACKING\"").unwrap();
+            let appendix = contents.find("\"APPENDIX A. WARRANTY\"").unwrap();
+            assert!(chapter < appendix);
+            assert!(!contents.contains("FOREWORD"));
+
+            assert!(match manual.find(&Path::from("/MANUAL/CHAPTER_02")) {
+                Some(Selected::Text(text)) => *text == "CHAPTER 2. CARE\n\nNEVER MICROWAVE FROG.\n",
+                _ => false,
+            });
+            assert!(manual.find(&Path::from("/MANUAL/FOREWORD")).is_none());
+            assert!(manual.find(&Path::from("/MANUAL/CHAPTER_03")).is_none());
+        }
+
+        #[test]
+        fn searches_find_lines() {
+            let manual = manual();
+            let found = items(manual.find(&Path::new("/MANUAL/SEARCH", Some("frog"))));
+            let found = format!("{:?}", found);
+            assert!(found.contains("val: \"/MANUAL/CHAPTER_01\", extra: None }, desc: \"CHAPTER 1. UNPACKING: TAKE OUT FROG.\""));
+            assert!(found.contains("val: \"/MANUAL/CHAPTER_02\", extra: None }, desc: \"CHAPTER 2. CARE: NEVER MICROWAVE FROG.\""));
+            assert!(!found.contains("APPENDIX"));
+
+            let found = format!("{:?}", items(manual.find(&Path::new("/MANUAL/SEARCH", Some("TOAD")))));
+            assert_eq!(found, "[Info { desc: \"THE MANUAL SAYS NOTHING ABOUT TOAD.\" }]");
+        }
+    }
+}
+
+mod binary_source {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use protocol::{self,MenuItem,Path,Selected};
+    use super::menu::{Source,MenuItemIter};
+
+
+    pub struct BinarySource {
+        path: Path,
+        desc: String,
+        file: PathBuf,
+        item_type: char,
+    }
+
+    impl BinarySource {
+        pub fn new<S: Into<String>, P: Into<PathBuf>>(path: Path, desc: S, file: P) -> BinarySource {
+            let file = file.into();
+            // Whatever we can't name is just binary
+            let item_type = file.to_str().and_then(protocol::binary_type).unwrap_or('9');
+            BinarySource {
+                path: path,
+                desc: desc.into(),
+                file: file,
+                item_type: item_type,
+            }
+        }
+    }
+
+    impl Source for BinarySource {
+        fn find(&self, path: &Path) -> Option<Selected> {
+            if self.path.val() != path.val() {
+                return None;
+            }
+
+            match super::read_file(&self.file) {
+                Ok(bytes) => Some(Selected::Binary(Box::new(bytes))),
+                Err(why) => {
+                    warn!("COULD NOT READ {:?}: {}", self.file, why);
+                    Some(Selected::Failed(Box::new(format!("FROG COULD NOT READ {}", path))))
+                },
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "binary"
+        }
+
+        fn size(&self, path: &Path) -> Option<usize> {
+            if self.path.val() != path.val() {
+                return None;
+            }
+            fs::metadata(&self.file).ok().map(|meta| meta.len() as usize)
+        }
+
+        fn menu_items(&self) -> MenuItemIter {
+            MenuItemIter::new(vec![MenuItem::binary(self.item_type, self.path.clone(), self.desc.clone()).unwrap()])
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::env;
+        use std::fs;
+        use std::io::Write;
+        use std::path::PathBuf;
+
+        use protocol::{MenuItem,Path,Selected};
+        use super::super::menu::Source;
+        use super::BinarySource;
+
+
+        fn blob(name: &str) -> PathBuf {
+            let file = env::temp_dir().join(format!("frog_gopher_binary_{}", name));
+            fs::File::create(&file).unwrap().write_all(b"\x7fFROG").unwrap();
+            file
+        }
+
+        #[test]
+        fn sends_the_file_as_it_is() {
+            let source = BinarySource::new(Path::from("/FIRMWARE"), "FIRMWARE.", blob("firmware.bin"));
+            assert!(match source.find(&Path::from("/FIRMWARE")) {
+                Some(Selected::Binary(bytes)) => *bytes == b"\x7fFROG".to_vec(),
+                _ => false,
+            });
+            assert!(source.find(&Path::from("/FIRMWARE/V2")).is_none());
+            assert!(source.find(&Path::from("/FIRM")).is_none());
+        }
+
+        #[test]
+        fn missing_files_are_failures() {
+            let missing = env::temp_dir().join("frog_gopher_no_such_blob");
+            let source = BinarySource::new(Path::from("/FIRMWARE"), "FIRMWARE.", missing);
+            assert!(match source.find(&Path::from("/FIRMWARE")) { Some(Selected::Failed(_)) => true, _ => false });
+        }
+
+        #[test]
+        fn files_too_big_to_send_are_failures() {
+            let file = env::temp_dir().join("frog_gopher_binary_huge.img");
+            fs::File::create(&file).unwrap().set_len(super::super::MAX_FILE_SIZE + 1).unwrap();
+            let source = BinarySource::new(Path::from("/FIRMWARE"), "FIRMWARE.", file.clone());
+            assert!(match source.find(&Path::from("/FIRMWARE")) { Some(Selected::Failed(_)) => true, _ => false });
+
+            fs::File::create(&file).unwrap().set_len(super::super::MAX_FILE_SIZE).unwrap();
+            assert!(match source.find(&Path::from("/FIRMWARE")) {
+                Some(Selected::Binary(bytes)) => bytes.len() as u64 == super::super::MAX_FILE_SIZE,
+                _ => false,
+            });
+            fs::remove_file(&file).unwrap();
+        }
+
+        #[test]
+        fn item_types_come_from_the_extension() {
+            let item_type = |name: &str| {
+                let items: Vec<MenuItem> = BinarySource::new(Path::from("/FROG"), "FROG.", blob(name)).menu_items().collect();
+                assert_eq!(items.len(), 1);
+                items[0].item_type()
+            };
+            assert_eq!(item_type("photo.jpg"), 'I');
+            assert_eq!(item_type("frog.gif"), 'g');
+            assert_eq!(item_type("pond.zip"), '5');
+            // Whatever we can't name is just binary
+            assert_eq!(item_type("frog.xyz"), '9');
+            assert_eq!(item_type("FROG"), '9');
+        }
+    }
+}
+
 mod tip_source {
     use rustc_serialize::json;
     use reqwest;
 
-    use std::io::Read;
+    use std::error;
+    use std::fmt;
+    use std::fs::File;
+    use std::io::{Read,Write};
     use std::io;
+    use std::path::PathBuf;
+    use std::sync::{Arc,RwLock,Weak};
+    use std::thread;
+    use std::time::{Duration,Instant};
+
+    use futures::{future,Future};
+    use tokio_tls::TlsConnector;
+    use url::Url;
 
     use protocol::{Menu,MenuItem,Path,Selected};
-    use super::menu::{MenuItemIter,Source};
+    use metrics::Metrics;
+    use http_get;
+    use super::menu::{Later,MenuItemIter,Source};
 
     use itertools::Itertools;
+    use rand::{self,Rng};
 
 
     static ROOT_PATH: &'static str = "/TIP/";
     static SEARCH_PATH: &'static str = "/TIP/SEARCH";
+    static SEARCH_DESC: &'static str = "SEARCH FOR A FROG TIP.";
+    // Not in any menu, but there for anyone who knows to ask
+    static RANDOM_PATH: &'static str = "/TIP/RANDOM";
 
     fn tips_into_menu_items(tips: &Vec<Tip>) -> Vec<MenuItem> {
         tips.into_iter()
@@ -216,6 +1357,7 @@ mod tip_source {
     enum TipPath {
         Tip(TipNum),
         Search(Option<String>),
+        Random,
         Unknown,
     }
 
@@ -224,6 +1366,8 @@ mod tip_source {
             let val = path.val();
             if val.starts_with(SEARCH_PATH) {
                 TipPath::Search(path.extra().map(|x| x.clone()))
+            } else if val == RANDOM_PATH {
+                TipPath::Random
             } else if val.starts_with(ROOT_PATH) {
                 match val.split("/").last() {
                     Some(num) => match num.parse::<TipNum>() {
@@ -239,7 +1383,7 @@ mod tip_source {
     }
 
     #[allow(dead_code)]
-    #[derive(RustcDecodable)]
+    #[derive(Clone,RustcDecodable,RustcEncodable)]
     struct Tip {
         approved: bool,
         moderated: bool,
@@ -270,77 +1414,287 @@ mod tip_source {
         fn items(&self) -> Vec<MenuItem> {
             tips_into_menu_items(&self.tips)
         }
+
+        fn size(&self, item: &MenuItem) -> Option<usize> {
+            match item.path().map(|path| TipPath::from(path.clone())) {
+                Some(TipPath::Tip(number)) => self.tips.iter()
+                                                       .find(|tip| tip.number == number)
+                                                       .map(|tip| tip.tip.len()),
+                _ => None,
+            }
+        }
     }
 
-    // Access tips
+    // Talk to frog.tips
 
-    pub struct TipSource {
+    // Searches block, and are only made in the background. Single tips are waited on by gophers,
+    // so they are asked for without blocking.
+    struct TipApi {
+        api_url: String,
         api_key: String,
         client: reqwest::Client,
+        tls: TlsConnector,
     }
 
-    impl TipSource {
-        pub fn new(api_key: String) -> TipSource {
-            let client = reqwest::Client::new().unwrap();
-            TipSource {
-                api_key: api_key,
-                client: client,
-            }
-        }
-
-        fn one_tip(&self, number: TipNum) -> Result<Option<Tip>, TipError> {
-            let url = format!("https://frog.tips/api/2/tips/{}", number);
-            let mut resp = try!(
-                self.client.get(&url)
-                           .header(reqwest::header::Authorization(self.api_key.clone()))
-                           .header(reqwest::header::Connection::close())
-                           .send());
+    impl TipApi {
+        fn one_tip_later(&self, number: TipNum) -> Box<Future<Item=Option<Tip>, Error=TipError> + Send> {
+            let url = format!("{}/tips/{}", self.api_url, number);
+            debug!("ASKING FROG.TIPS FOR TIP #{} AT {} WITHOUT WAITING.", number, url);
+            let url = match Url::parse(&url) {
+                Ok(url) => url,
+                Err(why) => return Box::new(future::err(TipError::Io(io::Error::new(io::ErrorKind::InvalidInput, why)))),
+            };
 
-            {
-                let status = resp.status();
-                if status != &reqwest::StatusCode::Ok {
-                    warn!("NO TIP FOUND BECAUSE: {:?}", status);
-                    return Ok(None);
+            Box::new(http_get::get(&url, &[("Authorization", &self.api_key)], &self.tls).map_err(TipError::from).and_then(|resp| {
+                match resp.status {
+                    200 => {},
+                    404 => return Ok(None),
+                    status => return Err(TipError::Status(reqwest::StatusCode::from_u16(status))),
                 }
-            }
 
-           let mut body = String::new();
-           try!(resp.read_to_string(&mut body));
-           let tip: Tip = try!(json::decode(&body));
-           Ok(Some(tip))
+                let tip: Tip = try!(json::decode(&String::from_utf8_lossy(&resp.body)));
+                Ok(Some(tip))
+            }))
         }
 
         fn all_tips(&self) -> Result<Vec<Tip>, TipError> {
-            self.search_tips(None)
-        }
-
-        fn search_tips(&self, text: Option<String>) -> Result<Vec<Tip>, TipError> {
             let query = SearchQuery {
                 approved: true,
                 tweeted: true,
-                tip: text,
+                tip: None,
             };
             let body = try!(json::encode(&query));
 
+            let url = format!("{}/tips/search", self.api_url);
+            debug!("ASKING FROG.TIPS FOR EVERY TWEETED TIP AT {}.", url);
             let mut resp = try!(
-                self.client.post("https://frog.tips/api/2/tips/search")
+                self.client.post(&url)
                            .body(body)
                            .header(reqwest::header::Authorization(self.api_key.clone()))
                            .header(reqwest::header::Connection::close())
                            .send());
 
             {
+                // Anything but a proper answer should leave the store as it was
                 let status = resp.status();
                 if status != &reqwest::StatusCode::Ok {
-                    warn!("NO TIP FOUND BECAUSE: {:?}", status);
-                    return Ok(vec![]);
+                    return Err(TipError::Status(*status));
                 }
             }
 
-            let mut body = String::new();
-            try!(resp.read_to_string(&mut body));
-            let results: SearchResults = try!(json::decode(&body));
-            Ok(results.results)
+            let mut body = String::new();
+            try!(resp.read_to_string(&mut body));
+            let results: SearchResults = try!(json::decode(&body));
+            debug!("FROG.TIPS SENT {} TIPS.", results.results.len());
+            Ok(results.results)
+        }
+    }
+
+    // Read tips from a file shaped like the API's, either a JSON array or one tip per line
+
+    struct TipDump {
+        file: PathBuf,
+    }
+
+    impl TipDump {
+        fn load(&self) -> Result<Vec<Tip>, TipError> {
+            debug!("READING TIPS FROM {}.", self.file.display());
+            let mut text = String::new();
+            try!(try!(File::open(&self.file)).read_to_string(&mut text));
+
+            if text.trim_left().starts_with('[') {
+                Ok(try!(json::decode(&text)))
+            } else {
+                let mut tips = vec![];
+                for line in text.lines().filter(|l| !l.trim().is_empty()) {
+                    tips.push(try!(json::decode(line)));
+                }
+                Ok(tips)
+            }
+        }
+
+        // Untweeted tips too, which the store keeps out of menus so this is otherwise just like
+        // searching the API for approved, tweeted tips
+        fn all_tips(&self) -> Result<Vec<Tip>, TipError> {
+            Ok(try!(self.load()).into_iter()
+                                .filter(|t| t.approved)
+                                .collect())
+        }
+    }
+
+    enum TipOrigin {
+        Api(TipApi),
+        Dump(TipDump),
+    }
+
+    impl TipOrigin {
+        fn all_tips(&self) -> Result<Vec<Tip>, TipError> {
+            match *self {
+                TipOrigin::Api(ref api) => api.all_tips(),
+                TipOrigin::Dump(ref dump) => dump.all_tips(),
+            }
+        }
+
+        // Times whatever went to frog.tips, and counts whatever went wrong
+        fn observed<T, F>(&self, metrics: &Metrics, call: &'static str, ask: F) -> Result<T, TipError>
+            where F: FnOnce(&TipOrigin) -> Result<T, TipError> {
+            let started = Instant::now();
+            let res = ask(self);
+            if let TipOrigin::Api(_) = *self {
+                metrics.asked_frog_tips(call, started.elapsed());
+            }
+            if let Err(ref why) = res {
+                metrics.tip_error(why.name(), &why.to_string());
+            }
+            res
+        }
+    }
+
+    // Write every approved, tweeted tip the API knows about as a dump, one tip per line
+    pub fn export_tips<W: Write>(api_url: String, api_key: String, out: &mut W) -> Result<usize, TipError> {
+        let api = TipApi {
+            api_url: api_url.trim_right_matches('/').to_string(),
+            api_key: api_key,
+            client: try!(reqwest::Client::new()),
+            tls: try!(http_get::tls()),
+        };
+
+        let tips = try!(api.all_tips());
+        for tip in tips.iter() {
+            try!(write!(out, "{}\n", try!(json::encode(tip))));
+        }
+
+        Ok(tips.len())
+    }
+
+    // Remember tips so visitors don't wait on frog.tips
+
+    struct TipStore {
+        tips: Vec<Tip>,
+        // Only dumps have these. They can be found by number, but never turn up in menus, searches
+        // or at random.
+        untweeted: Vec<Tip>,
+        refreshed: Option<Instant>,
+    }
+
+    impl TipStore {
+        fn refresh(origin: &TipOrigin, store: &RwLock<TipStore>, metrics: &Metrics) {
+            match origin.observed(metrics, "all_tips", TipOrigin::all_tips) {
+                Ok(tips) => {
+                    info!("REFRESHED {} TIPS.", tips.len());
+                    metrics.tips_refreshed(tips.len());
+                    let (tweeted, untweeted) = tips.into_iter().partition(|t| t.tweeted != 0);
+                    let mut store = store.write().unwrap();
+                    store.tips = tweeted;
+                    store.untweeted = untweeted;
+                    store.refreshed = Some(Instant::now());
+                },
+                Err(why) => {
+                    let store = store.read().unwrap();
+                    match store.refreshed {
+                        Some(when) => warn!("COULD NOT REFRESH TIPS, SERVING {} FROM {}S AGO: {:?}",
+                                            store.tips.len(), when.elapsed().as_secs(), why),
+                        None => warn!("COULD NOT REFRESH TIPS, HAVE NONE TO SERVE: {:?}", why),
+                    }
+                },
+            }
+        }
+
+        // Keeps refreshing until the source that owns the store is gone
+        fn keep_fresh(origin: Arc<TipOrigin>, store: Weak<RwLock<TipStore>>, interval: Duration, metrics: Arc<Metrics>)
+                      -> io::Result<thread::JoinHandle<()>> {
+            thread::Builder::new()
+                .name("TIP_REFRESHER".to_string())
+                .spawn(move || {
+                    loop {
+                        match store.upgrade() {
+                            Some(store) => TipStore::refresh(&origin, &store, &metrics),
+                            None => return,
+                        }
+                        thread::sleep(interval);
+                    }
+                })
+        }
+
+        fn find(&self, number: TipNum) -> Option<Tip> {
+            self.tips.iter()
+                     .chain(self.untweeted.iter())
+                     .find(|t| t.number == number)
+                     .cloned()
+        }
+
+        fn search(&self, text: Option<String>) -> Vec<Tip> {
+            match text {
+                Some(text) => {
+                    let text = text.to_uppercase();
+                    self.tips.iter()
+                             .filter(|t| t.tip.to_uppercase().contains(&text))
+                             .cloned()
+                             .collect()
+                },
+                None => self.tips.clone(),
+            }
+        }
+
+        fn random(&self) -> Option<Tip> {
+            rand::thread_rng().choose(&self.tips).cloned()
+        }
+    }
+
+    // Access tips
+
+    pub struct TipSource {
+        origin: Arc<TipOrigin>,
+        store: Arc<RwLock<TipStore>>,
+        metrics: Arc<Metrics>,
+    }
+
+    impl TipSource {
+        pub fn from_api(api_url: String, api_key: String, refresh: Option<Duration>, metrics: Arc<Metrics>) -> TipSource {
+            let client = reqwest::Client::new().unwrap();
+            TipSource::new(TipOrigin::Api(TipApi {
+                api_url: api_url.trim_right_matches('/').to_string(),
+                api_key: api_key,
+                client: client,
+                tls: http_get::tls().unwrap(),
+            }), refresh, metrics)
+        }
+
+        pub fn from_dump(file: PathBuf, refresh: Option<Duration>, metrics: Arc<Metrics>) -> TipSource {
+            TipSource::new(TipOrigin::Dump(TipDump {
+                file: file,
+            }), refresh, metrics)
+        }
+
+        // Without a refresh interval the tips are fetched once, before this returns, and never again
+        fn new(origin: TipOrigin, refresh: Option<Duration>, metrics: Arc<Metrics>) -> TipSource {
+            let origin = Arc::new(origin);
+            let store = Arc::new(RwLock::new(TipStore {
+                tips: vec![],
+                untweeted: vec![],
+                refreshed: None,
+            }));
+
+            match refresh {
+                Some(refresh) => if let Err(why) = TipStore::keep_fresh(origin.clone(), Arc::downgrade(&store), refresh, metrics.clone()) {
+                    error!("COULD NOT START REFRESHING TIPS: {}", why);
+                },
+                None => TipStore::refresh(&origin, &store, &metrics),
+            }
+
+            TipSource {
+                origin: origin,
+                store: store,
+                metrics: metrics,
+            }
+        }
+
+        fn all_tips(&self) -> Vec<Tip> {
+            self.search_tips(None)
+        }
+
+        fn search_tips(&self, text: Option<String>) -> Vec<Tip> {
+            self.store.read().unwrap().search(text)
         }
     }
 
@@ -348,27 +1702,22 @@ mod tip_source {
         fn find(&self, path: &Path) -> Option<Selected> {
             let tip_path = TipPath::from((*path).clone());
             match tip_path {
-                TipPath::Tip(num) => match self.one_tip(num) {
-                    Ok(Some(tip)) => {
+                // Tips frog.tips still has to be asked for are found later
+                TipPath::Tip(num) => match self.store.read().unwrap().find(num) {
+                    Some(tip) => {
                         Some(Selected::Text(Box::new(tip.tip)))
                     },
-                    Ok(None) => {
+                    None => {
                         warn!("TIP {} NOT FOUND.", num);
                         None
                     },
-                    Err(why) => {
-                        warn!("ERROR FETCHING TIP: {:?}", why);
-                        None
-                    },
                 },
-                TipPath::Search(text) => match self.search_tips(text) {
-                    Ok(tips) => {
-                        Some(Selected::TempMenu(Box::new(SearchResultsMenu { tips: tips })))
-                    },
-                    Err(why) => {
-                        warn!("ERROR SEARCHING FOR TIP: {:?}", why);
-                        None
-                    },
+                TipPath::Search(text) => {
+                    let tips = self.search_tips(text);
+                    Some(Selected::TempMenu(Box::new(SearchResultsMenu { tips: tips })))
+                },
+                TipPath::Random => {
+                    self.store.read().unwrap().random().map(|tip| Selected::Text(Box::new(tip.tip)))
                 },
                 _ => {
                     None
@@ -376,35 +1725,136 @@ mod tip_source {
             }
         }
 
+        fn kind(&self) -> &'static str {
+            "tips"
+        }
+
         fn menu_items(&self) -> MenuItemIter {
-            let mut vec = match self.all_tips() {
-                Ok(ref tips) => {
-                    tips_into_menu_items(tips)
-                },
-                Err(why) => {
-                    warn!("COULD NOT PROVIDE TIPS: {:?}", why);
-                    vec![]
-                }
-            };
+            let mut vec = tips_into_menu_items(&self.all_tips());
             vec.insert(0, MenuItem::Search {
                 path: Path::from(SEARCH_PATH),
-                desc: "SEARCH FOR A FROG TIP.".to_string(),
+                desc: SEARCH_DESC.to_string(),
             });
             vec.insert(0, MenuItem::Info {
                 desc: "\nINTERACT WITH ALL TWEETED FROG TIPS, SORTED FROM LATEST TO THE EARLIEST TWEETED.".to_string()
             });
             MenuItemIter::new(vec)
         }
+
+        fn is_costly(&self, path: &Path) -> bool {
+            match TipPath::from(path.clone()) {
+                TipPath::Unknown => false,
+                _ => true,
+            }
+        }
+
+        // Tips frog.tips still has to be asked for have no size yet
+        fn size(&self, path: &Path) -> Option<usize> {
+            match TipPath::from(path.clone()) {
+                TipPath::Tip(number) => self.store.read().unwrap().find(number).map(|tip| tip.tip.len()),
+                _ => None,
+            }
+        }
+
+        // Every tip is called the same thing, whether or not it was ever tweeted
+        fn describe(&self, path: &Path) -> Option<String> {
+            match TipPath::from(path.clone()) {
+                TipPath::Tip(number) => Some(format!("TIP #{}", number)),
+                TipPath::Search(_) => Some(SEARCH_DESC.to_string()),
+                _ => None,
+            }
+        }
+
+        // Only tips that never made it into the store have to be asked for
+        fn find_later(&self, path: &Path) -> Option<(Later, &'static str)> {
+            let number = match TipPath::from(path.clone()) {
+                TipPath::Tip(number) => number,
+                _ => return None,
+            };
+            let api = match *self.origin {
+                TipOrigin::Api(ref api) => api,
+                TipOrigin::Dump(_) => return None,
+            };
+            if self.store.read().unwrap().find(number).is_some() {
+                return None;
+            }
+
+            let metrics = self.metrics.clone();
+            let started = Instant::now();
+            Some((Box::new(api.one_tip_later(number).then(move |res| {
+                metrics.asked_frog_tips("one_tip", started.elapsed());
+                match res {
+                    Ok(Some(tip)) => Ok(Some(tip.tip)),
+                    Ok(None) => {
+                        warn!("TIP {} NOT FOUND.", number);
+                        Ok(None)
+                    },
+                    Err(why) => {
+                        metrics.tip_error(why.name(), &why.to_string());
+                        warn!("ERROR FETCHING TIP: {:?}", why);
+                        Err(format!("FROG COULD NOT FETCH TIP #{}", number))
+                    },
+                }
+            })), self.kind()))
+        }
     }
 
     #[derive(Debug)]
-    enum TipError {
+    pub enum TipError {
         Network(reqwest::Error),
+        Status(reqwest::StatusCode),
         Decoding(json::DecoderError),
-        Search(json::EncoderError),
+        Encoding(json::EncoderError),
         Io(io::Error),
     }
 
+    impl TipError {
+        // Short enough for a metric label
+        pub fn name(&self) -> &'static str {
+            match *self {
+                TipError::Network(_) => "network",
+                TipError::Status(_) => "status",
+                TipError::Decoding(_) => "decoding",
+                TipError::Encoding(_) => "encoding",
+                TipError::Io(_) => "io",
+            }
+        }
+    }
+
+    impl fmt::Display for TipError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                TipError::Network(ref err) => write!(f, "Network error: {}", err),
+                TipError::Status(ref status) => write!(f, "Unexpected status: {}", status),
+                TipError::Decoding(ref err) => write!(f, "Decoding error: {}", err),
+                TipError::Encoding(ref err) => write!(f, "Encoding error: {}", err),
+                TipError::Io(ref err) => write!(f, "IoError: {}", err),
+            }
+        }
+    }
+
+    impl error::Error for TipError {
+        fn description(&self) -> &str {
+            match *self {
+                TipError::Network(ref err) => err.description(),
+                TipError::Status(_) => "The API did not answer with 200 OK.",
+                TipError::Decoding(ref err) => err.description(),
+                TipError::Encoding(ref err) => err.description(),
+                TipError::Io(ref err) => err.description(),
+            }
+        }
+
+        fn cause(&self) -> Option<&error::Error> {
+            match *self {
+                TipError::Network(ref err) => Some(err),
+                TipError::Status(_) => None,
+                TipError::Decoding(ref err) => Some(err),
+                TipError::Encoding(ref err) => Some(err),
+                TipError::Io(ref err) => Some(err),
+            }
+        }
+    }
+
     impl From<reqwest::Error> for TipError {
         fn from(err: reqwest::Error) -> TipError {
             TipError::Network(err)
@@ -425,15 +1875,170 @@ mod tip_source {
 
     impl From<json::EncoderError> for TipError {
         fn from(err: json::EncoderError) -> TipError {
-            TipError::Search(err)
+            TipError::Encoding(err)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use reqwest;
+        use hyper::status::StatusCode;
+
+        use std::env;
+        use std::fs::File;
+        use std::io::Write;
+        use std::sync::RwLock;
+
+        use tokio::runtime::Runtime;
+
+        use metrics::Metrics;
+        use mock_frog_tips::{self,MockFrogTips,Reply};
+        use http_get;
+        use super::{TipApi,TipDump,TipError,TipOrigin,TipStore,export_tips};
+
+
+        fn api(mock: &MockFrogTips) -> TipApi {
+            TipApi {
+                api_url: mock.url(),
+                api_key: mock_frog_tips::API_KEY.to_string(),
+                client: reqwest::Client::new().unwrap(),
+                tls: http_get::tls().unwrap(),
+            }
+        }
+
+        #[test]
+        fn fetches_tips() {
+            let mock = MockFrogTips::start(mock_frog_tips::canned);
+            let api = api(&mock);
+
+            assert_eq!(api.all_tips().unwrap().len(), 3);
+        }
+
+        #[test]
+        fn fetches_tips_without_waiting() {
+            let mock = MockFrogTips::start(mock_frog_tips::canned);
+            let api = api(&mock);
+            let mut runtime = Runtime::new().unwrap();
+
+            assert_eq!(runtime.block_on(api.one_tip_later(4)).unwrap().unwrap().tip, "FROG WAS NEVER TWEETED.");
+            assert!(runtime.block_on(api.one_tip_later(5)).unwrap().is_none());
+        }
+
+        #[test]
+        fn bad_statuses_are_errors_for_searches() {
+            let mock = MockFrogTips::start(|_, _, _| Reply::status(StatusCode::InternalServerError));
+            match api(&mock).all_tips() {
+                Err(TipError::Status(StatusCode::InternalServerError)) => {},
+                other => panic!("unexpected: {:?}", other.map(|t| t.len())),
+            }
+        }
+
+        #[test]
+        fn bad_statuses_are_errors_for_tips() {
+            let mock = MockFrogTips::start(|_, path, _| match path {
+                "/api/2/tips/7" => Reply::status(StatusCode::InternalServerError),
+                _ => Reply::status(StatusCode::Unauthorized),
+            });
+            let api = api(&mock);
+            let mut runtime = Runtime::new().unwrap();
+
+            match runtime.block_on(api.one_tip_later(7)) {
+                Err(TipError::Status(StatusCode::InternalServerError)) => {},
+                other => panic!("unexpected: {:?}", other.map(|t| t.is_some())),
+            }
+            match runtime.block_on(api.one_tip_later(8)) {
+                Err(TipError::Status(StatusCode::Unauthorized)) => {},
+                other => panic!("unexpected: {:?}", other.map(|t| t.is_some())),
+            }
+        }
+
+        #[test]
+        fn malformed_json_is_a_decoding_error() {
+            let mock = MockFrogTips::start(|_, _, _| Reply::ok("{\"results\": [{\"number\": \"ONE\""));
+            let api = api(&mock);
+
+            match api.all_tips() {
+                Err(TipError::Decoding(_)) => {},
+                other => panic!("unexpected: {:?}", other.map(|t| t.len())),
+            }
+            match Runtime::new().unwrap().block_on(api.one_tip_later(1)) {
+                Err(TipError::Decoding(_)) => {},
+                other => panic!("unexpected: {:?}", other.map(|t| t.is_some())),
+            }
+        }
+
+        #[test]
+        fn exports_load_back_as_dumps() {
+            let mock = MockFrogTips::start(mock_frog_tips::canned);
+            let mut exported = vec![];
+            let count = export_tips(mock.url(), mock_frog_tips::API_KEY.to_string(), &mut exported).unwrap();
+            assert_eq!(count, 3);
+
+            let file = env::temp_dir().join("frog_gopher_export.jsonl");
+            File::create(&file).unwrap().write_all(&exported).unwrap();
+
+            let origin = TipOrigin::Dump(TipDump { file: file });
+            let store = RwLock::new(TipStore { tips: vec![], untweeted: vec![], refreshed: None });
+            TipStore::refresh(&origin, &store, &Metrics::new());
+            let store = store.read().unwrap();
+            assert_eq!(store.tips.len(), 3);
+            assert_eq!(store.find(2).unwrap().tip, "FROG IS A TOY.");
+        }
+
+        #[test]
+        fn stale_tips_are_kept_when_a_refresh_fails() {
+            let file = env::temp_dir().join("frog_gopher_stale.jsonl");
+            File::create(&file).unwrap()
+                .write_all(mock_frog_tips::tip_json(1, 100, "FROG IS NOT A TOY.").as_bytes()).unwrap();
+
+            let origin = TipOrigin::Dump(TipDump { file: file.clone() });
+            let store = RwLock::new(TipStore { tips: vec![], untweeted: vec![], refreshed: None });
+            let metrics = Metrics::new();
+            TipStore::refresh(&origin, &store, &metrics);
+            let refreshed = store.read().unwrap().refreshed;
+            assert!(refreshed.is_some());
+
+            File::create(&file).unwrap().write_all(b"FROG IS NOT JSON").unwrap();
+            TipStore::refresh(&origin, &store, &metrics);
+            let store = store.read().unwrap();
+            assert_eq!(store.find(1).unwrap().tip, "FROG IS NOT A TOY.");
+            assert_eq!(store.refreshed, refreshed);
+        }
+
+        #[test]
+        fn dumps_can_be_json_arrays() {
+            let file = env::temp_dir().join("frog_gopher_dump.json");
+            let json = format!("[{}, {}]",
+                               mock_frog_tips::tip_json(1, 100, "FROG IS NOT A TOY."),
+                               mock_frog_tips::tip_json(4, 0, "FROG WAS NEVER TWEETED."));
+            File::create(&file).unwrap().write_all(json.as_bytes()).unwrap();
+
+            let origin = TipOrigin::Dump(TipDump { file: file });
+            let store = RwLock::new(TipStore { tips: vec![], untweeted: vec![], refreshed: None });
+            TipStore::refresh(&origin, &store, &Metrics::new());
+            let store = store.read().unwrap();
+            assert_eq!(store.tips.len(), 1);
+            // Untweeted tips can be found, but not searched for
+            assert_eq!(store.find(4).unwrap().tip, "FROG WAS NEVER TWEETED.");
+            assert!(store.search(Some("TWEETED".to_string())).is_empty());
+            assert!(store.find(5).is_none());
         }
     }
 }
 
 mod menu {
+    use std::collections::HashMap;
+
+    use futures::Future;
+
     use protocol::{Selected,Menu,MenuItem,Path};
 
 
+    // The text a source will have for a path once whatever it is waiting on answers, or why it
+    // could not get at it
+    pub type Later = Box<Future<Item=Option<String>, Error=String> + Send>;
+
+
     pub struct AnyMenu {
         sources: Vec<Box<Source>>
     }
@@ -449,10 +2054,26 @@ mod menu {
             self.sources.push(Box::new(source));
         }
 
-        pub fn find(&self, path: &Path) -> Option<Selected> {
+        pub fn find_kind(&self, path: &Path) -> Option<(Selected, &'static str)> {
             info!("PATH: '{}'", path);
             self.sources.iter()
-                        .filter_map(|s| s.find(path))
+                        .filter_map(|s| s.find_kind(path))
+                        .nth(0)
+        }
+
+        pub fn is_costly(&self, path: &Path) -> bool {
+            self.sources.iter().any(|s| s.is_costly(path))
+        }
+
+        pub fn find_later(&self, path: &Path) -> Option<(Later, &'static str)> {
+            self.sources.iter()
+                        .filter_map(|s| s.find_later(path))
+                        .nth(0)
+        }
+
+        pub fn describe(&self, path: &Path) -> Option<String> {
+            self.sources.iter()
+                        .filter_map(|s| s.describe(path))
                         .nth(0)
         }
     }
@@ -463,6 +2084,53 @@ mod menu {
                         .flat_map(|s| s.menu_items())
                         .collect()
         }
+
+        fn size(&self, item: &MenuItem) -> Option<usize> {
+            item.path().and_then(|path| self.sources.iter()
+                                                    .filter_map(|s| s.size(path))
+                                                    .nth(0))
+        }
+    }
+
+    // Items worked out ahead of time, along with how big the ones that lead to something are
+    pub struct Listing {
+        items: Vec<MenuItem>,
+        // By selector
+        sizes: HashMap<String, usize>,
+    }
+
+    impl Listing {
+        // Nothing but info lines
+        pub fn lines(lines: Vec<String>) -> Listing {
+            Listing::sized(lines.into_iter().map(|line| MenuItem::Info {desc: line}).collect(), HashMap::new())
+        }
+
+        pub fn sized(items: Vec<MenuItem>, sizes: HashMap<String, usize>) -> Listing {
+            Listing {
+                items: items,
+                sizes: sizes,
+            }
+        }
+
+        // Other items that lead to the same places
+        pub fn with_items(&self, items: Vec<MenuItem>) -> Listing {
+            Listing::sized(items, self.sizes.clone())
+        }
+    }
+
+    impl Menu for Listing {
+        fn items(&self) -> Vec<MenuItem> {
+            self.items.clone()
+        }
+
+        fn size(&self, item: &MenuItem) -> Option<usize> {
+            item.path().and_then(|path| self.sizes.get(path.val()).cloned())
+        }
+    }
+
+    // Paths the same but for a trailing slash lead to the same place
+    pub fn same_path(path: &Path, other: &Path) -> bool {
+        path.val().trim_right_matches('/') == other.val().trim_right_matches('/')
     }
 
     pub struct MenuItemIter {
@@ -490,98 +2158,330 @@ mod menu {
     pub trait Source: Send {
         fn find(&self, path: &Path) -> Option<Selected>;
         fn menu_items(&self) -> MenuItemIter;
+
+        // What the hole config calls this kind of source
+        fn kind(&self) -> &'static str;
+
+        // Whether finding the path could mean asking frog.tips, which gophers get less of
+        fn is_costly(&self, _: &Path) -> bool {
+            false
+        }
+
+        // Finds the path, along with the kind of source that had it
+        fn find_kind(&self, path: &Path) -> Option<(Selected, &'static str)> {
+            self.find(path).map(|selected| (selected, self.kind()))
+        }
+
+        // For paths that would keep a thread waiting on frog.tips, finds them without blocking instead,
+        // along with the kind of source that will have them
+        fn find_later(&self, _: &Path) -> Option<(Later, &'static str)> {
+            None
+        }
+
+        // How big whatever is at the path is, if the source can say without finding it
+        fn size(&self, _: &Path) -> Option<usize> {
+            None
+        }
+
+        // What the source's menu calls the path
+        fn describe(&self, path: &Path) -> Option<String> {
+            self.menu_items()
+                .find(|item| item.path().map_or(false, |item_path| same_path(item_path, path)))
+                .map(|item| item.desc().to_string())
+        }
     }
 }
 
-use std::net::TcpStream;
-use std::io::Write;
-use std::io;
+use std::net::IpAddr;
+use std::fs::File;
+use std::io::{self,Read,Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize,Ordering};
+use std::time::Instant;
 
-use hyper::Url;
+use futures::{future,Async,Future};
+use tokio::io::{AsyncRead,AsyncWrite};
+use tokio_threadpool;
 
 use self::menu::AnyMenu;
+pub use self::menu::Listing;
 use self::tip_source::TipSource;
+pub use self::tip_source::{TipError,export_tips};
 use self::text_source::TextSource;
+use self::binary_source::BinarySource;
+use self::manual_source::ManualSource;
 use self::bogus_source::BogusSource;
 use self::info_source::InfoSource;
+use self::menu_source::MenuSource;
+use self::file_system_source::FileSystemSource;
 use self::url_source::UrlSource;
 use self::genuine_frog_source::GenuineFrogSource;
 use protocol::{Selector,Selected,Path,Protocol,ProtocolError,ExternalAddr};
+use hole_config::{HoleConfig,SourceConfig};
+use rate_limit::{Admission,RateLimiter};
+use access_log::{self,Served};
+use metrics::Metrics;
+use stats::{self,StatsConfig};
 
 
 static MAX_LINE_LEN: usize = 512;
 
-static README: &'static str = include_str!("../txt/README");
-static FROG_MODELS: &'static str = include_str!("../txt/FROG_MODELS");
-static FIRMWARE_V2: &'static str = include_str!("../txt/FIRMWARE_V2");
-static JOB_OPENINGS: &'static str = include_str!("../txt/JOB_OPENINGS");
-static JOB_OPENINGS_MOD_DATE: &'static str = "8 AUGUST 2016";
-static EVACUATION_PROCEDURE: &'static str = include_str!("../txt/EVACUATION_PROCEDURE");
+// Files are sent whole, so anything bigger is refused rather than held in memory for every
+// gopher that wants it
+const MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+// Finds that want a burrow while every one of them is taken
+static WAITING_FOR_A_BURROW: AtomicUsize = AtomicUsize::new(0);
+
+fn build_menu(sources: &Vec<SourceConfig>, frog_tips_api_key: &String, metrics: &Arc<Metrics>, keep_fresh: bool) -> AnyMenu {
+    let mut menu = AnyMenu::new();
+    let refreshed = |refresh| if keep_fresh { Some(refresh) } else { None };
+
+    for source in sources {
+        match source {
+            &SourceConfig::Info {ref text} => menu.push(
+                InfoSource::new(text.clone())),
+            &SourceConfig::Url {ref url, ref desc} => menu.push(
+                UrlSource::new(url.clone(), desc.clone())),
+            &SourceConfig::Text {ref path, ref desc, ref text} => menu.push(
+                TextSource::new(Path::from(path.clone()), desc.clone(), text.clone())),
+            &SourceConfig::Binary {ref path, ref desc, ref file} => menu.push(
+                BinarySource::new(Path::from(path.clone()), desc.clone(), file.clone())),
+            &SourceConfig::Bogus {ref path, ref desc} => menu.push(
+                BogusSource::new(Path::from(path.clone()), desc.clone())),
+            &SourceConfig::Files {ref path, ref desc, ref dir} => menu.push(
+                FileSystemSource::new(Path::from(path.clone()), desc.clone(), dir.clone())),
+            &SourceConfig::Manual {ref path, ref desc, ref pages} => menu.push(
+                ManualSource::new(Path::from(path.clone()), desc.clone(), pages)),
+            &SourceConfig::Menu {ref path, ref desc, ref sources} => menu.push(
+                MenuSource::new(Path::from(path.clone()), desc.clone(), build_menu(sources, frog_tips_api_key, metrics, keep_fresh))),
+            &SourceConfig::GenuineFrog {ref registry} => menu.push(
+                GenuineFrogSource::new(registry.clone())),
+            &SourceConfig::Tips {ref api_url, dump: None, refresh} => menu.push(
+                TipSource::from_api(api_url.clone(), frog_tips_api_key.clone(), refreshed(refresh), metrics.clone())),
+            &SourceConfig::Tips {dump: Some(ref dump), refresh, ..} => menu.push(
+                TipSource::from_dump(dump.clone(), refreshed(refresh), metrics.clone())),
+        };
+    }
+
+    menu
+}
+
+fn read_file(path: &::std::path::Path) -> io::Result<Vec<u8>> {
+    let file = try!(File::open(path));
+    let too_big = || io::Error::new(io::ErrorKind::InvalidData, format!("{} IS BIGGER THAN {} BYTES", path.display(), MAX_FILE_SIZE));
+    if try!(file.metadata()).len() > MAX_FILE_SIZE {
+        return Err(too_big());
+    }
+
+    // Files can grow between asking how big they are and reading them
+    let mut bytes = Vec::new();
+    try!(file.take(MAX_FILE_SIZE + 1).read_to_end(&mut bytes));
+    if bytes.len() as u64 > MAX_FILE_SIZE {
+        return Err(too_big());
+    }
+    Ok(bytes)
+}
+
+fn not_found<'a>(path: &Path) -> Selected<'a> {
+    Selected::Error(Box::new(format!("{} NOT FOUND", path)))
+}
+
+// Selectors are counted without search terms, which would never add up to anything
+fn selector_label(selector: &Selector) -> String {
+    match selector {
+        &Selector::Path(ref path) => path.val().clone(),
+        &Selector::Empty => String::new(),
+    }
+}
 
 pub struct Gopher {
     ext_addr: ExternalAddr,
     menu: AnyMenu,
+    aliases: Vec<(String, String)>,
+    limiter: RateLimiter,
+    metrics: Arc<Metrics>,
+    started: Instant,
+    stats: Option<StatsConfig>,
 }
 
 impl Gopher {
-    pub fn new(ext_addr: ExternalAddr, frog_tips_api_key: String) -> Gopher {
-        let mut menu = AnyMenu::new();
-
-        menu.push(
-            InfoSource::new(README));
-        menu.push(
-            UrlSource::new(Url::parse("https://frog.tips").unwrap(), "FROG TIPS MAIN WEBSPACE."));
-        menu.push(
-            UrlSource::new(Url::parse("https://github.com/FROG-TIPS").unwrap(), "FROG SYSTEMS TECHNICAL RESOURCES."));
-        menu.push(
-            UrlSource::new(Url::parse("http://hosting.frog.tips/rules.html").unwrap(), "FROG SYSTEMS (C) SONG CONTEST RULES."));
-        menu.push(
-            GenuineFrogSource::new());
-        menu.push(
-            UrlSource::new(Url::parse("https://twitter.com/FrogTips").unwrap(), "FROG SYSTEMS REAL-TIME WIRE SERVICE."));
-        menu.push(
-            InfoSource::new("IF YOU ARE EXPERIENCING AN EMERGENCY AT OUR MCMURDO BASE OF OPERATIONS,\nPLEASE SEND A WIRE TO THE ABOVE SERVICE IMMEDIATELY.\n"));
-        menu.push(
-            TextSource::new(Path::from("/JOB_OPENINGS"), "CURRENT FROG SYSTEMS INC. JOB OPENINGS.", JOB_OPENINGS));
-        menu.push(
-            InfoSource::new(format!("(UPDATED {})\n", JOB_OPENINGS_MOD_DATE)));
-        menu.push(
-            TextSource::new(Path::from("/README"), "READ ALL ABOUT FROG, THE LATEST SENSATION.", README));
-        menu.push(
-            BogusSource::new(Path::from("/USER_MANUAL"), "FROG USER MANUAL (EN) 17TH REV. INCLUDING APPENDICES."));
-        menu.push(
-            TextSource::new(Path::from("/FROG_MODELS"), "NON-CANON FROG MODEL LISTING.", FROG_MODELS));
-        menu.push(
-            TextSource::new(Path::from("/EVACUATION_PROCEDURE"), "OFFICIAL EVACUATION PROCEDURE.", EVACUATION_PROCEDURE));
-        menu.push(
-            TextSource::new(Path::from("/FIRMWARE_V2"), "FROG V2 FIRMWARE FOR ALL NON-OCEANIA MODELS", FIRMWARE_V2));
-        menu.push(
-            TipSource::new(frog_tips_api_key));
+    pub fn new(ext_addr: ExternalAddr, frog_tips_api_key: String, hole: &HoleConfig) -> Gopher {
+        Gopher::build(ext_addr, frog_tips_api_key, hole, true)
+    }
+
+    // For a gopher that answers once and goes away. Tips are fetched before this returns instead
+    // of in the background, and never refreshed.
+    pub fn once(ext_addr: ExternalAddr, frog_tips_api_key: String, hole: &HoleConfig) -> Gopher {
+        Gopher::build(ext_addr, frog_tips_api_key, hole, false)
+    }
 
+    fn build(ext_addr: ExternalAddr, frog_tips_api_key: String, hole: &HoleConfig, keep_fresh: bool) -> Gopher {
+        let metrics = Arc::new(Metrics::new());
         Gopher {
             ext_addr: ext_addr,
-            menu: menu,
+            menu: build_menu(&hole.sources, &frog_tips_api_key, &metrics, keep_fresh),
+            aliases: hole.aliases.clone(),
+            limiter: RateLimiter::unlimited(),
+            metrics: metrics,
+            started: Instant::now(),
+            stats: None,
         }
     }
 
-    pub fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
-        let resp = {
-            // FIXME: This protocol contains state that should not be shared
-            // However, it seems silly to create a new struct every time
-            let mut protocol = Protocol::new(&self.ext_addr, MAX_LINE_LEN);
+    // Nobody sees the stats unless this is called
+    pub fn set_stats(&mut self, stats: StatsConfig) {
+        self.stats = Some(stats);
+    }
 
-            let selected = match try!(protocol.read(&mut stream)) {
-                Selector::Path(ref path) => self.menu.find(path)
-                                                     .unwrap_or(
-                                                         Selected::Error(
-                                                             Box::new(format!("{} NOT FOUND", path)))),
-                Selector::Empty => Selected::ForeverMenu(&self.menu),
-            };
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    // Gophers are never limited unless this is called
+    pub fn set_rate_limiter(&mut self, limiter: RateLimiter) {
+        self.limiter = limiter;
+    }
+
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    pub fn admit(&self, peer: &IpAddr) -> Admission {
+        self.limiter.admit(peer)
+    }
+
+    fn protocol(&self) -> Protocol {
+        Protocol::new(&self.ext_addr, MAX_LINE_LEN)
+    }
+
+    pub fn ext_addr(&self) -> &ExternalAddr {
+        &self.ext_addr
+    }
+
+    // Where an old selector lives now, search terms and all
+    fn aliased(&self, selector: &Selector) -> Option<Selector> {
+        let path = match selector {
+            &Selector::Path(ref path) => path,
+            &Selector::Empty => return None,
+        };
+        let val = path.val().trim_right_matches('/');
+        self.aliases.iter()
+                    .find(|&&(ref from, _)| from.trim_right_matches('/') == val)
+                    .map(|&(_, ref to)| Selector::Path(Path::new(to.clone(), path.extra().cloned())))
+    }
+
+    // Whatever the menu has for the selector, whichever protocol asked
+    pub fn find(&self, selector: &Selector) -> Selected {
+        if let Some(aliased) = self.aliased(selector) {
+            return self.find(&aliased);
+        }
+
+        let (selected, source) = match selector {
+            &Selector::Path(ref path) => self.menu.find_kind(path)
+                                                  .unwrap_or((not_found(path), "none")),
+            &Selector::Empty => (Selected::ForeverMenu(&self.menu), "root"),
+        };
+        self.counted(selector, source);
+        selected
+    }
+
+    fn counted(&self, selector: &Selector, source: &'static str) {
+        self.metrics.request(&selector_label(selector), source);
+        if let (&Selector::Path(ref path), "tips") | (&Selector::Path(ref path), "genuine_frog") = (selector, source) {
+            if let Some(terms) = path.extra() {
+                self.metrics.searched(source, terms);
+            }
+        }
+    }
+
+    // What the gopher gets instead, if it may not have what it asked for. A gopher nobody knows
+    // the address of is never shown the stats, and has no bucket to run out of tips from.
+    fn gate(&self, peer: Option<IpAddr>, selector: &Selector) -> Option<Selected<'static>> {
+        if let (&Selector::Path(ref path), Some(ref peer)) = (selector, peer) {
+            if self.stats.as_ref().map_or(false, |stats| stats.shows(peer, path)) {
+                return Some(Selected::TempMenu(Box::new(stats::stats(self.started, &self.metrics))));
+            }
+
+            if self.menu.is_costly(path) && !self.limiter.admit_tip(peer) {
+                self.metrics.request(&selector_label(selector), "rate_limit");
+                return Some(Selected::SlowDown);
+            }
+        }
+        None
+    }
+
+    // Finds what the gopher asked for, unless it has been asking for tips too often, and hands it
+    // to `then`. Tips frog.tips has to be asked for are waited on without tying up a thread, and
+    // anything else is found in a burrow
+    pub fn find_then<T, F>(gopher: Arc<Gopher>, peer: Option<IpAddr>, selector: Selector, then: F) -> Box<Future<Item=T, Error=io::Error> + Send>
+        where T: Send + 'static, F: FnOnce(&Gopher, &Selector, Selected) -> io::Result<T> + Send + 'static {
+        let selector = gopher.aliased(&selector).unwrap_or(selector);
+        if let Some(turned_away) = gopher.gate(peer, &selector) {
+            return Box::new(future::result(then(&gopher, &selector, turned_away)));
+        }
 
-            try!(protocol.write(&mut stream, &selected))
+        let later = match selector {
+            Selector::Path(ref path) => gopher.menu.find_later(path),
+            Selector::Empty => None,
         };
+        if let Some((later, source)) = later {
+            return Box::new(later.then(move |text| {
+                let selected = match (text, &selector) {
+                    (Ok(Some(text)), _) => Selected::Text(Box::new(text)),
+                    (Err(why), _) => Selected::Failed(Box::new(why)),
+                    (Ok(None), &Selector::Path(ref path)) => not_found(path),
+                    (Ok(None), &Selector::Empty) => unreachable!("only paths are found later"),
+                };
+                gopher.counted(&selector, if let Selected::Error(_) = selected { "none" } else { source });
+                then(&gopher, &selector, selected)
+            }));
+        }
+
+        let mut then = Some(then);
+        in_a_burrow(move || {
+            let selected = gopher.find(&selector);
+            (then.take().expect("found twice"))(&gopher, &selector, selected)
+        })
+    }
+
+    // Everything the gopher gets back for what it asked for
+    fn answer(&self, protocol: &mut Protocol, selector: &Selector, selected: Selected) -> Result<(Vec<u8>, Served), ProtocolError> {
+        if let (&Selector::Path(ref path), true) = (selector, protocol.wants_attributes()) {
+            if let Some(desc) = self.menu.describe(path) {
+                protocol.described_as(desc);
+            }
+        }
+
+        let mut resp = Vec::new();
+        if let Err(why) = protocol.write(&mut resp, &selected) {
+            self.metrics.protocol_error(why.name(), &why.to_string());
+            return Err(why);
+        }
+        Ok((resp, Served::found(selector, &selected, access_log::gopher_binary_type(selector))))
+    }
+
+    // Reads what the gopher wants and answers it, without tying up a thread while it makes up its mind
+    pub fn respond<S>(gopher: Arc<Gopher>, stream: S, peer: Option<IpAddr>) -> Box<Future<Item=Served, Error=io::Error> + Send>
+        where S: AsyncRead + AsyncWrite + Send + 'static {
+        let reader = gopher.clone();
+        let read = gopher.protocol().read(stream).map_err(move |why| {
+            reader.metrics.protocol_error(why.name(), &why.to_string());
+            io::Error::from(why)
+        });
+        let answered = read.and_then(move |(stream, mut protocol, selector)| {
+            Gopher::find_then(gopher, peer, selector, move |gopher, selector, selected| {
+                gopher.answer(&mut protocol, selector, selected).map_err(io::Error::from)
+            }).map(move |(resp, served)| (stream, resp, served))
+        });
+
+        Box::new(answered.and_then(|(stream, resp, served)| hang_up(stream, resp, served)))
+    }
 
-        Ok(resp)
+    // Sends the gopher away without listening to what it wanted
+    pub fn turn_away<W: Write>(&self, mut stream: W, why: &str) -> io::Result<()> {
+        let mut protocol = self.protocol();
+        Ok(try!(protocol.write(&mut stream, &Selected::Error(Box::new(why.to_string())))))
     }
 }
 
@@ -592,3 +2492,244 @@ impl From<ProtocolError> for io::Error {
 }
 
 unsafe impl Sync for Gopher {}
+
+// Sends whatever the frontend made of the answer and hangs up, counting what was sent
+pub fn hang_up<S>(stream: S, resp: Vec<u8>, served: Served) -> Box<Future<Item=Served, Error=io::Error> + Send>
+    where S: AsyncWrite + Send + 'static {
+    Box::new(::tokio::io::write_all(stream, resp)
+                 .and_then(|(stream, resp)| ::tokio::io::shutdown(stream).map(move |_| served.sent(resp.len()))))
+}
+
+// Counts a find as waiting for a burrow until it gets one or gives up
+struct InLine;
+
+impl InLine {
+    fn join() -> InLine {
+        WAITING_FOR_A_BURROW.fetch_add(1, Ordering::SeqCst);
+        InLine
+    }
+}
+
+impl Drop for InLine {
+    fn drop(&mut self) {
+        WAITING_FOR_A_BURROW.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn waiting_for_a_burrow() -> usize {
+    WAITING_FOR_A_BURROW.load(Ordering::SeqCst)
+}
+
+// Sources may wait on the disk, so let them block somewhere harmless
+pub fn in_a_burrow<T, F>(mut f: F) -> Box<Future<Item=T, Error=io::Error> + Send>
+    where T: Send + 'static, F: FnMut() -> io::Result<T> + Send + 'static {
+    let mut in_line = None;
+    Box::new(future::poll_fn(move || match tokio_threadpool::blocking(|| f()) {
+        Ok(Async::Ready(res)) => {
+            in_line.take();
+            res.map(Async::Ready)
+        },
+        Ok(Async::NotReady) => {
+            in_line.get_or_insert_with(InLine::join);
+            Ok(Async::NotReady)
+        },
+        Err(why) => Err(io::Error::new(io::ErrorKind::Other, why)),
+    }))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read,Write};
+    use std::net::{TcpListener,TcpStream};
+    use std::path;
+    use std::sync::{Arc,Mutex};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration,Instant};
+
+    use futures::Future;
+    use hyper::method::Method;
+    use hyper::status::StatusCode;
+    use tokio::runtime;
+
+    use mock_frog_tips::{self,MockFrogTips,Reply};
+    use hole_config::HoleConfig;
+    use protocol::ExternalAddr;
+    use gopher_pool::GopherPool;
+    use super::{in_a_burrow,waiting_for_a_burrow,Gopher};
+
+
+    fn hole(mock: &MockFrogTips) -> HoleConfig {
+        let hole = format!("[[source]]\nkind = \"menu\"\npath = \"/TIP\"\ndesc = \"FROG TIPS.\"\n\n  \
+                            [[source.source]]\n  kind = \"tips\"\n  api = \"{}\"\n  refresh = 3600\n", mock.url());
+        HoleConfig::parse(&hole, path::Path::new(".")).unwrap()
+    }
+
+    fn gopher(mock: &MockFrogTips) -> Arc<Gopher> {
+        let ext_addr = ExternalAddr::new("127.0.0.1", 70);
+        Arc::new(Gopher::new(ext_addr, mock_frog_tips::API_KEY.to_string(), &hole(mock)))
+    }
+
+    fn ask(gopher: &Arc<Gopher>, line: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let pool = GopherPool::new(gopher.clone(), 1, 1, 1).unwrap();
+
+        let mut stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        pool.dispatch(listener.accept().unwrap().0).unwrap();
+
+        write!(stream, "{}\r\n", line).unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+        pool.join();
+        resp
+    }
+
+    // Answers with `route`, and tells us whenever the store has asked for every tip
+    fn watched<F>(route: F) -> (MockFrogTips, mpsc::Receiver<()>)
+        where F: Fn(&Method, &str, &str) -> Reply + Send + Sync + 'static {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let mock = MockFrogTips::start(move |method, path, body| {
+            let reply = route(method, path, body);
+            if path.ends_with("/search") {
+                let _ = tx.lock().unwrap().send(());
+            }
+            reply
+        });
+        (mock, rx)
+    }
+
+    fn wait_for_tips(gopher: &Arc<Gopher>) -> String {
+        let started = Instant::now();
+        loop {
+            let menu = ask(gopher, "/TIP");
+            if menu.contains("TIP #") || started.elapsed() > Duration::from_secs(5) {
+                return menu;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn finds_wait_in_line_for_a_burrow() {
+        let mut runtime = runtime::Builder::new().blocking_threads(1).build().unwrap();
+        let (started, has_started) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+        runtime.spawn(in_a_burrow(move || {
+            let _ = started.send(());
+            let _ = released.recv();
+            Ok(())
+        }).map_err(|_| ()));
+        has_started.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        runtime.spawn(in_a_burrow(|| Ok(())).map_err(|_| ()));
+        let waiting = Instant::now();
+        while waiting_for_a_burrow() == 0 && waiting.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(waiting_for_a_burrow() > 0);
+
+        release.send(()).unwrap();
+        runtime.shutdown_on_idle().wait().unwrap();
+    }
+
+    #[test]
+    fn tips_come_from_the_api() {
+        let mock = MockFrogTips::start(mock_frog_tips::canned);
+        let gopher = gopher(&mock);
+
+        let menu = wait_for_tips(&gopher);
+        let order: Vec<&str> = menu.lines()
+                                   .filter(|l| l.starts_with("0TIP #"))
+                                   .map(|l| l.split('\t').next().unwrap())
+                                   .collect();
+        assert_eq!(order, vec!["0TIP #2", "0TIP #3", "0TIP #1"]);
+
+        assert_eq!(ask(&gopher, "/TIP/3"), "NEVER MICROWAVE FROG.\r\n.\r\n");
+        assert_eq!(ask(&gopher, "/TIP/4"), "FROG WAS NEVER TWEETED.\r\n.\r\n");
+        assert_eq!(ask(&gopher, "/TIP/5"), "3/TIP/5 NOT FOUND\r\n.\r\n");
+
+        let results = ask(&gopher, "/TIP/SEARCH\tMICROWAVE");
+        assert!(results.starts_with("0TIP #3\t/TIP/3\t"));
+        assert_eq!(results.lines().count(), 2);
+
+        // Only tweeted tips come up at random
+        let random = ask(&gopher, "/TIP/RANDOM");
+        assert!(["FROG IS NOT A TOY.\r\n.\r\n", "FROG IS A TOY.\r\n.\r\n", "NEVER MICROWAVE FROG.\r\n.\r\n"].contains(&random.as_str()));
+    }
+
+    #[test]
+    fn gophers_answering_once_have_tips_already() {
+        let mock = MockFrogTips::start(mock_frog_tips::canned);
+        let ext_addr = ExternalAddr::new("127.0.0.1", 70);
+        let gopher = Arc::new(Gopher::once(ext_addr, mock_frog_tips::API_KEY.to_string(), &hole(&mock)));
+
+        assert!(ask(&gopher, "/TIP").contains("0TIP #3\t/TIP/3\t"));
+    }
+
+    #[test]
+    fn gopher_plus_tips_are_sent_whole() {
+        let mock = MockFrogTips::start(|method, path, body| match path {
+            "/api/2/tips/5" => Reply::ok(mock_frog_tips::tip_json(5, 0, ".FROG.\\nRIBBIT.")),
+            _ => mock_frog_tips::canned(method, path, body),
+        });
+        let gopher = gopher(&mock);
+
+        // Nothing to stop the client at a period, so it has to be counted after the CRLFs go in
+        assert_eq!(ask(&gopher, "/TIP/5\t+"), "+17\r\n.FROG.\r\nRIBBIT.\r\n");
+    }
+
+    #[test]
+    fn gopher_plus_attributes_come_from_the_menu() {
+        let mock = MockFrogTips::start(mock_frog_tips::canned);
+        let gopher = gopher(&mock);
+        wait_for_tips(&gopher);
+
+        // "NEVER MICROWAVE FROG." fits in a kilobyte
+        let attributes = ask(&gopher, "/TIP\t$");
+        assert!(attributes.contains("+INFO: 0TIP #3\t/TIP/3\t127.0.0.1\t70\t+\r\n"));
+        assert!(attributes.contains("+VIEWS:\r\n text/plain: <1k>\r\n"));
+
+        let attributes = ask(&gopher, "/TIP/3\t!");
+        assert!(attributes.starts_with("+-1\r\n+INFO: 0TIP #3\t/TIP/3\t"));
+        let attributes = ask(&gopher, "/TIP\t!");
+        assert!(attributes.starts_with("+-1\r\n+INFO: 1FROG TIPS.\t/TIP\t"));
+    }
+
+    #[test]
+    fn api_trouble_is_not_a_missing_tip() {
+        let mock = MockFrogTips::start(|method, path, body| match path {
+            "/api/2/tips/7" => Reply::status(StatusCode::InternalServerError),
+            "/api/2/tips/8" => Reply::status(StatusCode::Unauthorized),
+            _ => mock_frog_tips::canned(method, path, body),
+        });
+        let gopher = gopher(&mock);
+
+        assert_eq!(ask(&gopher, "/TIP/7"), "3FROG COULD NOT FETCH TIP #7\r\n.\r\n");
+        assert_eq!(ask(&gopher, "/TIP/8"), "3FROG COULD NOT FETCH TIP #8\r\n.\r\n");
+        assert_eq!(ask(&gopher, "/TIP/9"), "3/TIP/9 NOT FOUND\r\n.\r\n");
+    }
+
+    #[test]
+    fn api_errors_leave_an_empty_store() {
+        let (mock, searched) = watched(|_, _, _| Reply::status(StatusCode::ServiceUnavailable));
+        let gopher = gopher(&mock);
+        searched.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        let menu = ask(&gopher, "/TIP");
+        assert!(menu.contains("7SEARCH FOR A FROG TIP."));
+        assert!(!menu.contains("TIP #"));
+        assert_eq!(ask(&gopher, "/TIP/1"), "3FROG COULD NOT FETCH TIP #1\r\n.\r\n");
+    }
+
+    #[test]
+    fn malformed_json_leaves_an_empty_store() {
+        let (mock, searched) = watched(|_, _, _| Reply::ok("FROG IS NOT JSON"));
+        let gopher = gopher(&mock);
+        searched.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        assert!(!ask(&gopher, "/TIP").contains("TIP #"));
+        assert_eq!(ask(&gopher, "/TIP/2"), "3FROG COULD NOT FETCH TIP #2\r\n.\r\n");
+    }
+}